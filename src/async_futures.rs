@@ -0,0 +1,74 @@
+use std::{io, ops::Range};
+
+use futures_io::{AsyncRead, AsyncSeek};
+use futures_util::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::{
+    assemble, digest::Digest, hashing::Scheme, Imprint, ImprintError, Imprinter, Sampling,
+};
+
+impl Imprint {
+    /// Imprints the content of a futures-io reader, from its start.
+    ///
+    /// If `len` is `None`, the length of the content is found by seeking to the end.
+    pub async fn from_futures(
+        reader: impl AsyncRead + AsyncSeek + Unpin,
+        len: Option<u64>,
+    ) -> Result<Self, ImprintError> {
+        Imprinter::new().imprint_futures(reader, len).await
+    }
+}
+
+impl Imprinter {
+    /// Imprints the content of a futures-io reader, from its start.
+    ///
+    /// The result is identical to that of `Imprinter::imprint_reader`.
+    pub async fn imprint_futures(
+        &mut self,
+        mut reader: impl AsyncRead + AsyncSeek + Unpin,
+        len: Option<u64>,
+    ) -> Result<Imprint, ImprintError> {
+        let len = match len {
+            Some(len) => len,
+            None => reader.seek(io::SeekFrom::End(0)).await?,
+        };
+
+        let (sampling, scheme, buffer) = self.parts();
+        imprint_sampled(&mut reader, buffer, len, sampling, scheme)
+            .await
+            .map_err(|e| ImprintError::reading(e, len))
+    }
+}
+
+async fn imprint_sampled(
+    reader: &mut (impl AsyncRead + AsyncSeek + Unpin),
+    buf: &mut [u8],
+    len: u64,
+    sampling: Sampling,
+    scheme: Scheme,
+) -> io::Result<Imprint> {
+    let mut hashes = Vec::new();
+    for (role, window) in sampling.windows(len, &scheme) {
+        hashes.push(hash_window(reader, buf, window, scheme.hasher(role)).await?);
+    }
+    Ok(assemble(len, sampling, &scheme, hashes))
+}
+
+async fn hash_window<D: Digest>(
+    reader: &mut (impl AsyncRead + AsyncSeek + Unpin),
+    buf: &mut [u8],
+    window: Range<u64>,
+    mut hasher: D,
+) -> io::Result<D::Output> {
+    reader.seek(io::SeekFrom::Start(window.start)).await?;
+
+    let mut len = window.end - window.start;
+    while len > 0 {
+        let chunk = len.min(buf.len() as u64);
+        let buf = &mut buf[..chunk as usize];
+        reader.read_exact(buf).await?;
+        hasher.update(buf);
+        len -= chunk;
+    }
+    Ok(hasher.finalize())
+}
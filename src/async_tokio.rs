@@ -0,0 +1,153 @@
+use std::{io, ops::Range, path::Path};
+
+use tokio::{
+    fs::{self, File},
+    io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt},
+};
+
+use crate::{
+    assemble, digest::Digest, hashing::Scheme, stamp::Stamp, Imprint, ImprintError, Imprinter,
+    Sampling,
+};
+
+impl Imprint {
+    /// Imprints a file without blocking the async runtime.
+    pub async fn new_async(path: impl AsRef<Path>) -> Result<Self, ImprintError> {
+        Imprinter::new().imprint_async(path).await
+    }
+
+    /// Imprints the content of a tokio reader, from its start.
+    ///
+    /// If `len` is `None`, the length of the content is found by seeking to the end.
+    pub async fn from_tokio(
+        reader: impl AsyncRead + AsyncSeek + Unpin,
+        len: Option<u64>,
+    ) -> Result<Self, ImprintError> {
+        Imprinter::new().imprint_tokio(reader, len).await
+    }
+}
+
+impl Imprinter {
+    /// Imprints a file without blocking the async runtime.
+    ///
+    /// The result is identical to that of `Imprinter::imprint`.
+    pub async fn imprint_async(&mut self, path: impl AsRef<Path>) -> Result<Imprint, ImprintError> {
+        let path = path.as_ref();
+        self.imprint_file_async(path)
+            .await
+            .map_err(|e| e.with_path(path))
+    }
+
+    async fn imprint_file_async(&mut self, path: &Path) -> Result<Imprint, ImprintError> {
+        let mut attempts = 0;
+        loop {
+            match self.imprint_file_once_async(path).await {
+                Err(e) if e.is_unstable() && attempts < self.retries => attempts += 1,
+                result => return result,
+            }
+        }
+    }
+
+    async fn imprint_file_once_async(&mut self, path: &Path) -> Result<Imprint, ImprintError> {
+        ImprintError::check_file(path, &fs::metadata(path).await?)?;
+
+        let mut file = File::open(path).await?;
+        let before = Stamp::new(&file.metadata().await?);
+        let imprint = self.imprint_tokio(&mut file, Some(before.len)).await?;
+        before.check(&Stamp::new(&file.metadata().await?))?;
+
+        Ok(imprint)
+    }
+
+    /// Imprints the content of a tokio reader, from its start.
+    ///
+    /// The result is identical to that of `Imprinter::imprint_reader`.
+    pub async fn imprint_tokio(
+        &mut self,
+        mut reader: impl AsyncRead + AsyncSeek + Unpin,
+        len: Option<u64>,
+    ) -> Result<Imprint, ImprintError> {
+        let len = match len {
+            Some(len) => len,
+            None => reader.seek(io::SeekFrom::End(0)).await?,
+        };
+
+        let (sampling, scheme, buffer) = self.parts();
+        imprint_sampled(&mut reader, buffer, len, sampling, scheme)
+            .await
+            .map_err(|e| ImprintError::reading(e, len))
+    }
+}
+
+async fn imprint_sampled(
+    reader: &mut (impl AsyncRead + AsyncSeek + Unpin),
+    buf: &mut [u8],
+    len: u64,
+    sampling: Sampling,
+    scheme: Scheme,
+) -> io::Result<Imprint> {
+    let mut hashes = Vec::new();
+    for (role, window) in sampling.windows(len, &scheme) {
+        hashes.push(hash_window(reader, buf, window, scheme.hasher(role)).await?);
+    }
+    Ok(assemble(len, sampling, &scheme, hashes))
+}
+
+async fn hash_window<D: Digest>(
+    reader: &mut (impl AsyncRead + AsyncSeek + Unpin),
+    buf: &mut [u8],
+    window: Range<u64>,
+    mut hasher: D,
+) -> io::Result<D::Output> {
+    reader.seek(io::SeekFrom::Start(window.start)).await?;
+
+    let mut len = window.end - window.start;
+    while len > 0 {
+        let chunk = len.min(buf.len() as u64);
+        let buf = &mut buf[..chunk as usize];
+        reader.read_exact(buf).await?;
+        hasher.update(buf);
+        len -= chunk;
+    }
+    Ok(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Interior;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn tokio_imprints_match_sync_imprints() {
+        let content: Vec<u8> = (0..0x90000u32).map(|i| (i * 7 + i / 251) as u8).collect();
+        let interiors = [
+            Interior::None,
+            Interior::Even {
+                count: 5,
+                size: 1000,
+            },
+            Interior::Derived {
+                count: 3,
+                size: 4096,
+            },
+        ];
+
+        for interior in interiors {
+            for len in [0, 1, 0x20000, 0x20001, 0x40000, 0x40001, content.len()] {
+                let content = &content[..len];
+                let mut imprinter = Imprinter::new().interior(interior);
+                let expected = imprinter.imprint_memory(content).unwrap();
+                let actual =
+                    block_on(imprinter.imprint_tokio(io::Cursor::new(content), None)).unwrap();
+                assert_eq!(actual, expected, "{:?} at length {}", interior, len);
+            }
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    path::PathBuf,
+    sync::{mpsc, Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+use crate::{Imprint, ImprintError, Imprinter};
+
+type Job = (usize, PathBuf);
+type Outcome = (usize, PathBuf, Result<Imprint, ImprintError>);
+
+/// Imprints many files in parallel on a bounded pool of threads.
+///
+/// Each thread owns a clone of the imprinter, so sample buffers are reused from one file to the
+/// next rather than allocated per file.
+#[derive(Clone, Debug)]
+pub struct Batch {
+    imprinter: Imprinter,
+    threads: usize,
+    order: Order,
+}
+
+/// The order in which a batch yields its results.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum Order {
+    /// Results are yielded as soon as each file is imprinted.
+    #[default]
+    Completion,
+
+    /// Results are yielded in the order the paths were given.
+    Input,
+}
+
+/// An iterator over the results of a batch, as `(path, result)` pairs.
+///
+/// Paths are taken from the input only as results are consumed, so at most a small multiple
+/// of the number of threads are queued or held at any time. Dropping the iterator stops the
+/// batch once the files already queued are imprinted.
+pub struct ImprintMany<I> {
+    paths: I,
+    order: Order,
+    capacity: usize,
+    next_index: usize,
+    next_yield: usize,
+    in_flight: usize,
+    pending: HashMap<usize, (PathBuf, Result<Imprint, ImprintError>)>,
+    jobs: Option<mpsc::Sender<Job>>,
+    results: mpsc::Receiver<Outcome>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Batch {
+            imprinter: Imprinter::new(),
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            order: Order::Completion,
+        }
+    }
+
+    /// Sets the imprinter used to imprint each file.
+    pub fn imprinter(mut self, imprinter: Imprinter) -> Self {
+        self.imprinter = imprinter;
+        self
+    }
+
+    /// Sets the number of threads. The default is the available parallelism of the machine.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Sets the order in which results are yielded. The default is `Order::Completion`.
+    pub fn order(mut self, order: Order) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Starts imprinting the files at the given paths.
+    pub fn imprint_many<P: Into<PathBuf>, I: IntoIterator<Item = P>>(
+        &self,
+        paths: I,
+    ) -> ImprintMany<I::IntoIter> {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let (outcomes, results) = mpsc::channel();
+        let queue = Arc::new(Mutex::new(queue));
+
+        let workers = (0..self.threads)
+            .map(|_| {
+                let mut imprinter = self.imprinter.clone();
+                let queue = Arc::clone(&queue);
+                let outcomes = outcomes.clone();
+                thread::spawn(move || loop {
+                    // The lock is released before imprinting, once the job is received.
+                    let job = queue.lock().unwrap().recv();
+                    let (index, path) = match job {
+                        Ok(job) => job,
+                        Err(_) => return,
+                    };
+
+                    let result = imprinter.imprint(&path);
+                    if outcomes.send((index, path, result)).is_err() {
+                        return;
+                    }
+                })
+            })
+            .collect();
+
+        ImprintMany {
+            paths: paths.into_iter(),
+            order: self.order,
+            capacity: self.threads * 2,
+            next_index: 0,
+            next_yield: 0,
+            in_flight: 0,
+            pending: HashMap::new(),
+            jobs: Some(jobs),
+            results,
+            workers,
+        }
+    }
+}
+
+impl Default for Batch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Into<PathBuf>, I: Iterator<Item = P>> ImprintMany<I> {
+    /// Queues paths until the batch holds as many files as it allows.
+    fn fill(&mut self) {
+        let jobs = match &self.jobs {
+            Some(jobs) => jobs,
+            None => return,
+        };
+
+        while self.in_flight < self.capacity {
+            let path = match self.paths.next() {
+                Some(path) => path.into(),
+                None => {
+                    self.jobs = None;
+                    return;
+                }
+            };
+
+            if jobs.send((self.next_index, path)).is_err() {
+                self.jobs = None;
+                return;
+            }
+            self.next_index += 1;
+            self.in_flight += 1;
+        }
+    }
+
+    fn receive(&mut self) -> Option<Outcome> {
+        if self.in_flight == 0 {
+            return None;
+        }
+        self.results.recv().ok()
+    }
+}
+
+impl<P: Into<PathBuf>, I: Iterator<Item = P>> Iterator for ImprintMany<I> {
+    type Item = (PathBuf, Result<Imprint, ImprintError>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.fill();
+
+        let (path, result) = match self.order {
+            Order::Completion => {
+                let (_, path, result) = self.receive()?;
+                (path, result)
+            }
+            Order::Input => loop {
+                if let Some(outcome) = self.pending.remove(&self.next_yield) {
+                    self.next_yield += 1;
+                    break outcome;
+                }
+                let (index, path, result) = self.receive()?;
+                self.pending.insert(index, (path, result));
+            },
+        };
+
+        self.in_flight -= 1;
+        Some((path, result))
+    }
+}
+
+impl<I> fmt::Debug for ImprintMany<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImprintMany")
+            .field("order", &self.order)
+            .field("threads", &self.workers.len())
+            .field("in_flight", &self.in_flight)
+            .field("finished_input", &self.jobs.is_none())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<I> Drop for ImprintMany<I> {
+    fn drop(&mut self) {
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path, time::Duration};
+
+    use super::*;
+
+    /// Creates a directory of files, and returns their paths interleaved with missing ones.
+    fn paths(dir: &Path, count: usize) -> Vec<PathBuf> {
+        fs::create_dir_all(dir).unwrap();
+        (0..count)
+            .map(|i| {
+                let path = dir.join(i.to_string());
+                if i % 3 != 0 {
+                    fs::write(&path, i.to_string()).unwrap();
+                }
+                path
+            })
+            .collect()
+    }
+
+    fn temp(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("imprint-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn input_order_is_kept_despite_failures() {
+        let dir = temp("batch-input");
+        let paths = paths(&dir, 50);
+        let batch = Batch::new().threads(4).order(Order::Input);
+
+        let results: Vec<_> = batch.imprint_many(&paths).collect();
+        let yielded: Vec<_> = results.iter().map(|(path, _)| path.clone()).collect();
+        assert_eq!(yielded, paths);
+        for (i, (path, result)) in results.iter().enumerate() {
+            match result {
+                Ok(imprint) => assert_eq!(*imprint, Imprint::new(path).unwrap()),
+                Err(_) => assert_eq!(i % 3, 0),
+            }
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn completion_order_yields_every_result() {
+        let dir = temp("batch-completion");
+        let paths = paths(&dir, 50);
+        let batch = Batch::new().threads(4);
+
+        let mut yielded: Vec<_> = batch.imprint_many(&paths).map(|(path, _)| path).collect();
+        yielded.sort();
+        let mut expected = paths.clone();
+        expected.sort();
+        assert_eq!(yielded, expected);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dropping_a_partial_batch_finishes() {
+        let dir = temp("batch-drop");
+        let paths = paths(&dir, 200);
+
+        let (done, finished) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            let mut results = Batch::new().threads(2).imprint_many(paths);
+            assert!(results.next().is_some());
+            drop(results);
+            done.send(()).unwrap();
+        });
+        finished.recv_timeout(Duration::from_secs(30)).unwrap();
+        handle.join().unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn imprint_many_is_debug() {
+        let results = Batch::new().threads(1).imprint_many(Vec::<PathBuf>::new());
+        assert!(format!("{:?}", results).starts_with("ImprintMany {"));
+    }
+}
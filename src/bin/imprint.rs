@@ -0,0 +1,571 @@
+//! Command-line interface to the imprint library.
+//!
+//! # Exit codes
+//!
+//! - `0`: success. For `dupes`, `compare` and `verify`, no duplicates or differences were found.
+//! - `1`: duplicates or differences were found.
+//! - `2`: some file could not be examined, or the command could not run at all.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{Args, Parser, Subcommand};
+use imprint::{
+    verify_same_content, Change, DuplicateFinder, Glob, Imprint, ImprintError, Manifest,
+    ProgressiveImprint, Walker,
+};
+use serde_json::{json, Value};
+
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  success; for dupes, compare and verify, no duplicates or differences were found
+  1  duplicates or differences were found
+  2  some file could not be examined, or the command could not run at all";
+
+#[derive(Parser)]
+#[command(name = "imprint", version, about, after_help = EXIT_CODES)]
+struct Cli {
+    /// Write JSON instead of human-readable output.
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the imprints of files, walking any directories.
+    Print {
+        /// Print the canonical form of each imprint rather than its head hash.
+        #[arg(long)]
+        long: bool,
+
+        #[command(flatten)]
+        walk: WalkArgs,
+
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Find files with identical content, walking any directories.
+    Dupes {
+        /// Confirm duplicates by hashing their entire content.
+        #[arg(long)]
+        confirm: bool,
+
+        #[command(flatten)]
+        walk: WalkArgs,
+
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Compare two files, or two directory trees.
+    Compare {
+        /// Confirm files in two trees that share an imprint by hashing their entire content.
+        ///
+        /// Two files are always compared in full.
+        #[arg(long)]
+        full: bool,
+
+        #[command(flatten)]
+        walk: WalkArgs,
+
+        left: PathBuf,
+        right: PathBuf,
+    },
+
+    /// Show how one tree differs from another, detecting renamed and copied files.
+    ///
+    /// Each side is either a directory or a manifest written by `imprint snapshot`.
+    Diff {
+        #[command(flatten)]
+        walk: WalkArgs,
+
+        old: PathBuf,
+        new: PathBuf,
+    },
+
+    /// Write a manifest of the files under a directory.
+    Snapshot {
+        /// Write the manifest to a file rather than to standard output.
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        walk: WalkArgs,
+
+        root: PathBuf,
+    },
+
+    /// Verify the files under a directory against a manifest written by `imprint snapshot`.
+    Verify {
+        #[command(flatten)]
+        walk: WalkArgs,
+
+        manifest: PathBuf,
+        root: PathBuf,
+    },
+}
+
+#[derive(Args)]
+struct WalkArgs {
+    /// Follow symbolic links.
+    #[arg(short = 'L', long)]
+    follow_links: bool,
+
+    /// Stay on the filesystem of each directory given.
+    #[arg(short = 'x', long)]
+    one_file_system: bool,
+
+    /// Honor .gitignore files.
+    #[arg(long)]
+    gitignore: bool,
+
+    /// Examine only files matching the glob.
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<Glob>,
+
+    /// Skip files and directories matching the glob.
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<Glob>,
+}
+
+/// What a command found, which decides the exit code.
+#[derive(Default)]
+struct Outcome {
+    differences: bool,
+    errors: Vec<ImprintError>,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Print { long, walk, paths } => print(&walk, &paths, long, cli.json),
+        Command::Dupes {
+            confirm,
+            walk,
+            paths,
+        } => dupes(&walk, &paths, confirm, cli.json),
+        Command::Compare {
+            full,
+            walk,
+            left,
+            right,
+        } => compare(&walk, &left, &right, full, cli.json),
+        Command::Diff { walk, old, new } => diff(&walk, &old, &new, cli.json),
+        Command::Snapshot { output, walk, root } => {
+            snapshot(&walk, &root, output.as_deref(), cli.json)
+        }
+        Command::Verify {
+            walk,
+            manifest,
+            root,
+        } => verify(&walk, &manifest, &root, cli.json),
+    };
+
+    match result {
+        Ok(outcome) => {
+            if !cli.json {
+                for error in &outcome.errors {
+                    eprintln!("imprint: {}", error);
+                }
+            }
+            match (outcome.errors.is_empty(), outcome.differences) {
+                (false, _) => ExitCode::from(2),
+                (true, true) => ExitCode::from(1),
+                (true, false) => ExitCode::SUCCESS,
+            }
+        }
+        Err(e) => {
+            eprintln!("imprint: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn print(walk: &WalkArgs, paths: &[PathBuf], long: bool, json: bool) -> io::Result<Outcome> {
+    let mut outcome = Outcome::default();
+    let mut files = Vec::new();
+    for entry in walk.walker(paths).walk() {
+        match entry {
+            Ok(entry) => files.push((entry.path, entry.imprint)),
+            Err(e) => outcome.errors.push(e),
+        }
+    }
+    files.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+    if json {
+        let files: Vec<_> = files
+            .iter()
+            .map(|(path, imprint)| json!({ "path": json_path(path), "imprint": imprint }))
+            .collect();
+        emit(json!({ "files": files, "errors": json_errors(&outcome.errors) }))?;
+    } else {
+        for (path, imprint) in &files {
+            if long {
+                println!("{:#} {}", imprint, path.display());
+            } else {
+                println!("{} {}", imprint, path.display());
+            }
+        }
+    }
+    Ok(outcome)
+}
+
+fn dupes(walk: &WalkArgs, paths: &[PathBuf], confirm: bool, json: bool) -> io::Result<Outcome> {
+    let mut outcome = Outcome::default();
+    let files = walk.walker(paths).files().filter_map(|file| match file {
+        Ok((path, _)) => Some(path),
+        Err(e) => {
+            outcome.errors.push(e);
+            None
+        }
+    });
+
+    let duplicates = DuplicateFinder::new().confirm(confirm).find(files);
+    let reclaimable = duplicates.reclaimable();
+    let (groups, errors) = duplicates.into_parts();
+    outcome.errors.extend(errors);
+    outcome.differences = !groups.is_empty();
+
+    if json {
+        let groups: Vec<_> = groups
+            .iter()
+            .map(|group| {
+                json!({
+                    "len": group.len(),
+                    "files": group.files(),
+                    "reclaimable": group.reclaimable(),
+                    "paths": json_paths(group.paths()),
+                })
+            })
+            .collect();
+        emit(json!({
+            "groups": groups,
+            "reclaimable": reclaimable,
+            "errors": json_errors(&outcome.errors),
+        }))?;
+    } else {
+        for group in &groups {
+            println!(
+                "{} files of {} bytes, {} bytes reclaimable",
+                group.files(),
+                group.len(),
+                group.reclaimable()
+            );
+            for path in group.paths() {
+                println!("  {}", path.display());
+            }
+            println!();
+        }
+        println!("{} groups, {} bytes reclaimable", groups.len(), reclaimable);
+    }
+    Ok(outcome)
+}
+
+fn compare(
+    walk: &WalkArgs,
+    left: &Path,
+    right: &Path,
+    full: bool,
+    json: bool,
+) -> io::Result<Outcome> {
+    let left_meta = fs::metadata(left).map_err(at(left))?;
+    let right_meta = fs::metadata(right).map_err(at(right))?;
+    match (left_meta.is_dir(), right_meta.is_dir()) {
+        (false, false) => compare_files(left, right, json),
+        (true, true) => compare_trees(walk, left, right, full, json),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "cannot compare a file with a directory",
+        )),
+    }
+}
+
+fn compare_files(left: &Path, right: &Path, json: bool) -> io::Result<Outcome> {
+    let mut outcome = Outcome::default();
+    let comparison = ProgressiveImprint::open(left)
+        .and_then(|mut left| left.compare(&mut ProgressiveImprint::open(right)?));
+    let comparison = match comparison {
+        Ok(comparison) => comparison,
+        Err(e) => {
+            outcome.errors.push(e);
+            if json {
+                emit(json!({ "errors": json_errors(&outcome.errors) }))?;
+            }
+            return Ok(outcome);
+        }
+    };
+
+    outcome.differences = !comparison.equal;
+    let level = format!("{:?}", comparison.level).to_lowercase();
+    if json {
+        emit(json!({ "equal": comparison.equal, "level": level, "errors": [] }))?;
+    } else if comparison.equal {
+        println!("identical");
+    } else {
+        println!("different {}", level);
+    }
+    Ok(outcome)
+}
+
+fn compare_trees(
+    walk: &WalkArgs,
+    left: &Path,
+    right: &Path,
+    full: bool,
+    json: bool,
+) -> io::Result<Outcome> {
+    let mut outcome = Outcome::default();
+    let left_files = tree(walk, left, &mut outcome.errors);
+    let mut right_files = tree(walk, right, &mut outcome.errors);
+
+    let mut removed = Vec::new();
+    let mut modified = Vec::new();
+    for (path, imprint) in left_files {
+        let other = match right_files.remove(&path) {
+            Some(other) => other,
+            None => {
+                removed.push(path);
+                continue;
+            }
+        };
+
+        let equal = imprint == other
+            && (!full
+                || match verify_same_content(left.join(&path), right.join(&path)) {
+                    Ok(equal) => equal,
+                    Err(e) => {
+                        outcome.errors.push(e);
+                        continue;
+                    }
+                });
+        if !equal {
+            modified.push(path);
+        }
+    }
+    let added: Vec<_> = right_files.into_keys().collect();
+
+    outcome.differences = !(removed.is_empty() && added.is_empty() && modified.is_empty());
+    if json {
+        emit(json!({
+            "removed": json_paths(&removed),
+            "added": json_paths(&added),
+            "modified": json_paths(&modified),
+            "errors": json_errors(&outcome.errors),
+        }))?;
+    } else {
+        let mut lines: Vec<_> = removed
+            .iter()
+            .map(|path| ('-', path))
+            .chain(added.iter().map(|path| ('+', path)))
+            .chain(modified.iter().map(|path| ('M', path)))
+            .collect();
+        lines.sort_unstable_by(|a, b| a.1.cmp(b.1));
+        for (status, path) in lines {
+            println!("{} {}", status, path.display());
+        }
+    }
+    Ok(outcome)
+}
+
+/// Imprints the files under `root`, keyed by their paths relative to it.
+fn tree(
+    walk: &WalkArgs,
+    root: &Path,
+    errors: &mut Vec<ImprintError>,
+) -> BTreeMap<PathBuf, Imprint> {
+    let mut files = BTreeMap::new();
+    for entry in walk.walker(&[root.into()]).walk() {
+        match entry {
+            Ok(entry) => {
+                let path = entry.path.strip_prefix(root).unwrap_or(&entry.path);
+                files.insert(path.into(), entry.imprint);
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    files
+}
+
+fn diff(walk: &WalkArgs, old: &Path, new: &Path, json: bool) -> io::Result<Outcome> {
+    let mut outcome = Outcome::default();
+    let old = manifest(walk, old, &mut outcome.errors)?;
+    let new = manifest(walk, new, &mut outcome.errors)?;
+    let diff = old.diff(&new);
+    outcome.differences = !diff.is_empty();
+
+    if json {
+        let changes: Vec<_> = diff
+            .changes()
+            .iter()
+            .map(|change| {
+                json!({
+                    "change": status(change).1,
+                    "path": json_path(change.path()),
+                    "from": change.source().map(json_path),
+                })
+            })
+            .collect();
+        emit(json!({ "changes": changes, "errors": json_errors(&outcome.errors) }))?;
+    } else {
+        for change in &diff {
+            match change.source() {
+                Some(from) => println!(
+                    "{} {} -> {}",
+                    status(change).0,
+                    from.display(),
+                    change.path().display()
+                ),
+                None => println!("{} {}", status(change).0, change.path().display()),
+            }
+        }
+    }
+    Ok(outcome)
+}
+
+/// Reads a manifest, or snapshots a directory.
+fn manifest(walk: &WalkArgs, path: &Path, errors: &mut Vec<ImprintError>) -> io::Result<Manifest> {
+    if fs::metadata(path).map_err(at(path))?.is_dir() {
+        let (manifest, snapshot_errors) = walk.walker(&[path.into()]).snapshot();
+        errors.extend(snapshot_errors);
+        Ok(manifest)
+    } else {
+        read_manifest(path)
+    }
+}
+
+/// The short and long names of a change.
+fn status(change: &Change) -> (char, &'static str) {
+    match change {
+        Change::Added { .. } => ('A', "added"),
+        Change::Removed { .. } => ('D', "removed"),
+        Change::Modified { .. } => ('M', "modified"),
+        Change::Renamed { .. } => ('R', "renamed"),
+        Change::Copied { .. } => ('C', "copied"),
+    }
+}
+
+fn snapshot(
+    walk: &WalkArgs,
+    root: &Path,
+    output: Option<&Path>,
+    json: bool,
+) -> io::Result<Outcome> {
+    if json && output.is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--json requires --output for snapshot",
+        ));
+    }
+
+    let (manifest, errors) = walk.walker(&[root.into()]).snapshot();
+    match output {
+        Some(output) => {
+            let file = File::create(output).map_err(at(output))?;
+            manifest.write(BufWriter::new(file)).map_err(at(output))?
+        }
+        None => manifest.write(io::stdout().lock())?,
+    }
+
+    if json {
+        emit(json!({ "files": manifest.len(), "errors": json_errors(&errors) }))?;
+    }
+    Ok(Outcome {
+        differences: false,
+        errors,
+    })
+}
+
+fn verify(walk: &WalkArgs, manifest: &Path, root: &Path, json: bool) -> io::Result<Outcome> {
+    let manifest = read_manifest(manifest)?;
+    let verification = walk.walker(&[root.into()]).verify(&manifest);
+
+    if json {
+        emit(json!({
+            "unchanged": json_paths(verification.unchanged()),
+            "modified": json_paths(verification.modified()),
+            "added": json_paths(verification.added()),
+            "missing": json_paths(verification.missing()),
+            "errors": json_errors(verification.errors()),
+        }))?;
+    } else {
+        let mut lines: Vec<_> = verification
+            .missing()
+            .iter()
+            .map(|path| ('-', path))
+            .chain(verification.added().iter().map(|path| ('+', path)))
+            .chain(verification.modified().iter().map(|path| ('M', path)))
+            .collect();
+        lines.sort_unstable_by(|a, b| a.1.cmp(b.1));
+        for (status, path) in lines {
+            println!("{} {}", status, path.display());
+        }
+        println!("{} files unchanged", verification.unchanged().len());
+    }
+
+    let differences = !(verification.modified().is_empty()
+        && verification.added().is_empty()
+        && verification.missing().is_empty());
+    Ok(Outcome {
+        differences,
+        errors: verification.into_errors(),
+    })
+}
+
+impl WalkArgs {
+    fn walker(&self, roots: &[PathBuf]) -> Walker {
+        let mut walker = Walker::new(&roots[0])
+            .follow_links(self.follow_links)
+            .same_file_system(self.one_file_system)
+            .gitignore(self.gitignore);
+        for root in &roots[1..] {
+            walker = walker.root(root);
+        }
+        for glob in &self.include {
+            walker = walker.include(glob.clone());
+        }
+        for glob in &self.exclude {
+            walker = walker.exclude(glob.clone());
+        }
+        walker
+    }
+}
+
+/// Paths are written lossily, since JSON strings cannot hold arbitrary bytes.
+fn read_manifest(path: &Path) -> io::Result<Manifest> {
+    let file = File::open(path).map_err(at(path))?;
+    Manifest::read(BufReader::new(file)).map_err(at(path))
+}
+
+/// Names the path in an error, which would otherwise not say which file it concerns.
+fn at(path: &Path) -> impl FnOnce(io::Error) -> io::Error + '_ {
+    move |e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e))
+}
+
+fn json_path(path: &Path) -> Value {
+    Value::String(path.to_string_lossy().into_owned())
+}
+
+fn json_paths(paths: &[PathBuf]) -> Vec<Value> {
+    paths.iter().map(|path| json_path(path)).collect()
+}
+
+fn json_errors(errors: &[ImprintError]) -> Vec<Value> {
+    errors
+        .iter()
+        .map(|e| json!({ "path": e.path().map(json_path), "message": e.to_string() }))
+        .collect()
+}
+
+fn emit(value: Value) -> io::Result<()> {
+    println!("{}", serde_json::to_string_pretty(&value)?);
+    Ok(())
+}
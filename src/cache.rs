@@ -0,0 +1,529 @@
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    platform::{bytes_path, path_bytes, FileId},
+    stamp::{decode_time, encode_time, take, Stamp},
+    Imprint, ImprintError, Imprinter,
+};
+
+/// The bytes that begin a cache file, including the version of its format.
+const MAGIC: &[u8; 8] = b"impcach2";
+
+/// The bytes that began a cache file before records held the path of each file.
+const MAGIC_V1: &[u8; 8] = b"impcach1";
+
+/// The length of the checksum that ends each record.
+const CHECKSUM_LEN: usize = 8;
+
+/// A persistent cache of imprints, so that files unchanged since they were last imprinted need
+/// not be read again.
+///
+/// Entries are keyed by the device, inode, length, modification time and change time of each
+/// file, so an entry is used only while none of them has changed. An entry made with different
+/// sampling or a different hashing scheme from the cache's imprinter is never used.
+///
+/// The cache is stored as a log: new entries are appended, and an entry for a file supersedes
+/// any earlier one. Readers tolerate a record left incomplete by a concurrent writer, and
+/// `compact` rewrites the log with only current entries by replacing the file atomically, so
+/// any number of processes may read the cache while another updates it. A log found to end in
+/// an incomplete record is also rewritten, when the next entry is added. Each rewrite starts
+/// from the log as it is on disk, but entries appended by another process between that read
+/// and the replacement of the file are lost. They are never corrupted.
+///
+/// The log grows with every file imprinted and is never shrunk automatically: an entry for a
+/// file that is later deleted, replaced or modified stays in the log until `compact` is called.
+/// Caches of trees with many short-lived files should be compacted regularly.
+#[derive(Debug)]
+pub struct Cache {
+    path: PathBuf,
+    imprinter: Imprinter,
+    entries: HashMap<FileId, Entry>,
+    superseded: usize,
+    torn: bool,
+    log: Option<File>,
+}
+
+/// A cached imprint, with the file it was made from as it was when imprinted.
+#[derive(Debug)]
+struct Entry {
+    /// The canonical path of the file, used to check whether the entry is still current.
+    path: PathBuf,
+    stamp: Stamp,
+    imprint: Imprint,
+}
+
+impl Cache {
+    /// Opens the cache stored at `path`, which is created when the first entry is added.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, ImprintError> {
+        let path = path.into();
+        let mut cache = Cache {
+            path,
+            imprinter: Imprinter::new(),
+            entries: HashMap::new(),
+            superseded: 0,
+            torn: false,
+            log: None,
+        };
+
+        let bytes = match fs::read(&cache.path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(cache),
+            Err(e) => return Err(ImprintError::from(e).with_path(&cache.path)),
+        };
+        cache
+            .load(&bytes)
+            .map_err(|e| ImprintError::from(e).with_path(&cache.path))?;
+        Ok(cache)
+    }
+
+    /// Sets the imprinter used for files that are not cached.
+    pub fn imprinter(mut self, imprinter: Imprinter) -> Self {
+        self.imprinter = imprinter;
+        self
+    }
+
+    /// Imprints the file at `path`, reading it only if the cache holds no current entry for it.
+    ///
+    /// A new imprint is appended to the cache, unless the file changed while it was imprinted.
+    pub fn imprint(&mut self, path: impl AsRef<Path>) -> Result<Imprint, ImprintError> {
+        let path = path.as_ref();
+        let (id, stamp) = identify(path)?;
+        if let Some(imprint) = self.get(&id, &stamp) {
+            return Ok(imprint.clone());
+        }
+
+        let started = SystemTime::now();
+        let imprint = self.imprinter.imprint(path)?;
+        let (after_id, after) = identify(path)?;
+        if after_id == id && after == stamp && !stamp.is_racy(started) {
+            // A file whose path cannot be resolved is simply not cached.
+            if let Ok(path) = fs::canonicalize(path) {
+                let entry = Entry {
+                    path,
+                    stamp,
+                    imprint: imprint.clone(),
+                };
+                self.insert(id, entry)
+                    .map_err(|e| ImprintError::from(e).with_path(&self.path))?;
+            }
+        }
+        Ok(imprint)
+    }
+
+    /// The number of files with entries in the cache.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The number of records in the log that have been superseded by later ones, and would be
+    /// removed by compaction.
+    pub fn superseded(&self) -> usize {
+        self.superseded
+    }
+
+    /// Rewrites the log with only the entries that are still current.
+    ///
+    /// Superseded records are dropped, along with entries for files that have since been
+    /// deleted, replaced or modified, so every entry's file is examined. The new log is written
+    /// to a temporary file beside the cache and renamed over it, so concurrent readers see
+    /// either the old log or the new one.
+    pub fn compact(&mut self) -> Result<(), ImprintError> {
+        self.reload()
+            .map_err(|e| ImprintError::from(e).with_path(&self.path))?;
+        self.entries
+            .retain(|id, entry| match identify(&entry.path) {
+                Ok((current, stamp)) => current == *id && stamp == entry.stamp,
+                Err(_) => false,
+            });
+        self.rewrite()
+            .map_err(|e| ImprintError::from(e).with_path(&self.path))
+    }
+
+    fn get(&self, id: &FileId, stamp: &Stamp) -> Option<&Imprint> {
+        self.entries
+            .get(id)
+            .filter(|entry| entry.stamp == *stamp)
+            .map(|entry| &entry.imprint)
+            .filter(|imprint| self.imprinter.would_make(imprint))
+    }
+
+    fn insert(&mut self, id: FileId, entry: Entry) -> io::Result<()> {
+        let record = match record(&id, &entry) {
+            Some(record) => record,
+            None => return Ok(()),
+        };
+        // Records appended after an incomplete one would never be read, so the log is
+        // rewritten instead, keeping whatever other processes have added to it.
+        if self.torn {
+            self.reload()?;
+            self.entries.insert(id, entry);
+            return self.rewrite();
+        }
+
+        if self.entries.insert(id, entry).is_some() {
+            self.superseded += 1;
+        }
+
+        if self.log.is_none() {
+            let mut log = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            if log.metadata()?.len() == 0 {
+                log.write_all(MAGIC)?;
+            }
+            self.log = Some(log);
+        }
+
+        // Each record is appended with a single write, so a concurrent reader sees either all of
+        // it or an incomplete record at the end of the log.
+        self.log.as_mut().unwrap().write_all(&record)
+    }
+
+    fn rewrite(&mut self) -> io::Result<()> {
+        let mut buf = MAGIC.to_vec();
+        for (id, entry) in &self.entries {
+            buf.extend(record(id, entry).into_iter().flatten());
+        }
+
+        let temp = temp_path(&self.path);
+        let result = (|| {
+            let mut file = File::create(&temp)?;
+            file.write_all(&buf)?;
+            file.sync_all()?;
+            fs::rename(&temp, &self.path)
+        })();
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result?;
+
+        self.log = None;
+        self.superseded = 0;
+        self.torn = false;
+        Ok(())
+    }
+
+    /// Replaces the entries with those in the log as it is now, including any appended by other
+    /// processes since it was last read.
+    fn reload(&mut self) -> io::Result<()> {
+        self.entries.clear();
+        self.superseded = 0;
+        self.torn = false;
+        self.log = None;
+        match fs::read(&self.path) {
+            Ok(bytes) => self.load(&bytes),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn load(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let mut rest = match bytes.strip_prefix(&MAGIC[..]) {
+            Some(rest) => rest,
+            None if bytes.is_empty() => return Ok(()),
+            // An obsolete log is ignored, and replaced when the first entry is added.
+            None if bytes.starts_with(MAGIC_V1) => {
+                self.torn = true;
+                return Ok(());
+            }
+            None => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "not an imprint cache",
+                ))
+            }
+        };
+
+        while !rest.is_empty() {
+            let body = match next_record(&mut rest) {
+                Some(body) => body,
+                None => {
+                    self.torn = true;
+                    break;
+                }
+            };
+
+            // Records that cannot be decoded, such as those for algorithms not enabled in this
+            // build, are skipped.
+            if let Some((id, entry)) = decode(body) {
+                if self.entries.insert(id, entry).is_some() {
+                    self.superseded += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn identify(path: &Path) -> Result<(FileId, Stamp), ImprintError> {
+    let meta = fs::metadata(path).map_err(|e| ImprintError::from(e).with_path(path))?;
+    ImprintError::check_file(path, &meta)?;
+    let stamp = Stamp::new(&meta);
+    Ok(((stamp.dev, stamp.ino), stamp))
+}
+
+#[cfg(not(unix))]
+fn identify(path: &Path) -> Result<(FileId, Stamp), ImprintError> {
+    let meta = fs::metadata(path).map_err(|e| ImprintError::from(e).with_path(path))?;
+    ImprintError::check_file(path, &meta)?;
+    let id = fs::canonicalize(path).map_err(|e| ImprintError::from(e).with_path(path))?;
+    Ok((id, Stamp::new(&meta)))
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.tmp", std::process::id()));
+    path.with_file_name(name)
+}
+
+/// Frames an entry as its length, its body and a checksum of the body.
+fn record(id: &FileId, entry: &Entry) -> Option<Vec<u8>> {
+    let path = path_bytes(&entry.path)?;
+    let mut body = Vec::new();
+    body.extend_from_slice(&(path.len() as u32).to_le_bytes());
+    body.extend_from_slice(path);
+    encode_id(&mut body, id);
+    body.extend_from_slice(&entry.stamp.len.to_le_bytes());
+    encode_time(&mut body, entry.stamp.modified);
+    #[cfg(unix)]
+    {
+        body.extend_from_slice(&entry.stamp.ctime.0.to_le_bytes());
+        body.extend_from_slice(&entry.stamp.ctime.1.to_le_bytes());
+    }
+    body.extend_from_slice(&entry.imprint.to_bytes());
+
+    let mut record = Vec::with_capacity(4 + body.len() + CHECKSUM_LEN);
+    record.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    record.extend_from_slice(&body);
+    record.extend_from_slice(&checksum(&body));
+    Some(record)
+}
+
+/// Takes the body of the next record, or returns `None` if it is incomplete or corrupt.
+fn next_record<'a>(rest: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = u32::from_le_bytes(rest.get(..4)?.try_into().unwrap()) as usize;
+    let end = len.checked_add(4)?;
+    let body = rest.get(4..end)?;
+    let sum = rest.get(end..end.checked_add(CHECKSUM_LEN)?)?;
+    if sum != checksum(body) {
+        return None;
+    }
+    *rest = &rest[end + CHECKSUM_LEN..];
+    Some(body)
+}
+
+/// Decodes the body of a record.
+///
+/// The checksum only detects accidental corruption, and the cache file may have been written by
+/// anyone, so every field is checked rather than trusted.
+fn decode(mut body: &[u8]) -> Option<(FileId, Entry)> {
+    let path_len = u32::from_le_bytes(take(&mut body)?) as usize;
+    let path = bytes_path(body.get(..path_len)?.to_vec())?;
+    body = &body[path_len..];
+    let id = decode_id(&mut body, &path)?;
+    let len = u64::from_le_bytes(take(&mut body)?);
+    let modified = decode_time(&mut body)?;
+    #[cfg(unix)]
+    let ctime = (
+        i64::from_le_bytes(take(&mut body)?),
+        i64::from_le_bytes(take(&mut body)?),
+    );
+    let imprint = Imprint::from_bytes(body).ok()?;
+
+    #[cfg(unix)]
+    let stamp = Stamp {
+        len,
+        modified,
+        dev: id.0,
+        ino: id.1,
+        ctime,
+    };
+    #[cfg(not(unix))]
+    let stamp = Stamp { len, modified };
+    Some((
+        id,
+        Entry {
+            path,
+            stamp,
+            imprint,
+        },
+    ))
+}
+
+#[cfg(unix)]
+fn encode_id(buf: &mut Vec<u8>, id: &FileId) {
+    buf.extend_from_slice(&id.0.to_le_bytes());
+    buf.extend_from_slice(&id.1.to_le_bytes());
+}
+
+#[cfg(unix)]
+fn decode_id(body: &mut &[u8], _path: &Path) -> Option<FileId> {
+    Some((
+        u64::from_le_bytes(take(body)?),
+        u64::from_le_bytes(take(body)?),
+    ))
+}
+
+/// Without inodes, the identifier is the path, which is already recorded.
+#[cfg(not(unix))]
+fn encode_id(_buf: &mut Vec<u8>, _id: &FileId) {}
+
+#[cfg(not(unix))]
+fn decode_id(_body: &mut &[u8], path: &Path) -> Option<FileId> {
+    Some(path.into())
+}
+
+fn checksum(body: &[u8]) -> [u8; CHECKSUM_LEN] {
+    blake3::hash(body).as_bytes()[..CHECKSUM_LEN]
+        .try_into()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("imprint-{}-{}", name, std::process::id()))
+    }
+
+    fn cache() -> Cache {
+        Cache::open(temp("cache")).unwrap()
+    }
+
+    fn framed(body: &[u8]) -> Vec<u8> {
+        let mut record = (body.len() as u32).to_le_bytes().to_vec();
+        record.extend_from_slice(body);
+        record.extend_from_slice(&checksum(body));
+        record
+    }
+
+    #[test]
+    fn crafted_times_are_skipped() {
+        let imprint = Imprint::from_memory(b"content").unwrap();
+        let mut body = 4u32.to_le_bytes().to_vec();
+        body.extend_from_slice(b"file");
+        #[cfg(unix)]
+        encode_id(&mut body, &(1, 2));
+        #[cfg(not(unix))]
+        encode_id(&mut body, &PathBuf::from("file"));
+        body.extend_from_slice(&imprint.len().to_le_bytes());
+        body.push(1);
+        body.extend_from_slice(&u64::MAX.to_le_bytes());
+        body.extend_from_slice(&u32::MAX.to_le_bytes());
+        #[cfg(unix)]
+        body.extend_from_slice(&[0; 16]);
+        body.extend_from_slice(&imprint.to_bytes());
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&framed(&body));
+        let mut cache = cache();
+        cache.load(&bytes).unwrap();
+        assert!(cache.is_empty());
+        assert!(!cache.torn);
+    }
+
+    #[test]
+    fn oversized_lengths_are_torn() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(&[0; 32]);
+        let mut cache = cache();
+        cache.load(&bytes).unwrap();
+        assert!(cache.is_empty());
+        assert!(cache.torn);
+    }
+
+    #[test]
+    fn obsolete_logs_are_replaced() {
+        let mut bytes = MAGIC_V1.to_vec();
+        bytes.extend_from_slice(&[0; 32]);
+        let mut cache = cache();
+        cache.load(&bytes).unwrap();
+        assert!(cache.is_empty());
+        assert!(cache.torn);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn repairing_a_torn_log_keeps_other_writers_entries() {
+        let (log, file) = (temp("repair.cache"), temp("repair-file"));
+        let _ = fs::remove_file(&log);
+        fs::write(&file, b"content").unwrap();
+        let (id, stamp) = identify(&file).unwrap();
+        let entry = |name: &str| Entry {
+            path: name.into(),
+            stamp,
+            imprint: Imprint::from_memory(name.as_bytes()).unwrap(),
+        };
+
+        let mut writer = Cache::open(&log).unwrap();
+        writer.insert((id.0, 1), entry("first")).unwrap();
+
+        // As if the log was read while the writer was partway through appending a record.
+        let mut reader = Cache::open(&log).unwrap();
+        reader.torn = true;
+        writer.insert((id.0, 2), entry("second")).unwrap();
+        reader.insert((id.0, 3), entry("third")).unwrap();
+
+        let reopened = Cache::open(&log).unwrap();
+        assert_eq!(reopened.len(), 3);
+        assert!(!reopened.torn);
+        fs::remove_file(&log).unwrap();
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn compaction_prunes_deleted_and_modified_files() {
+        let (log, kept, deleted, modified) = (
+            temp("prune.cache"),
+            temp("prune-kept"),
+            temp("prune-deleted"),
+            temp("prune-modified"),
+        );
+        for path in &[&kept, &deleted, &modified] {
+            fs::write(path, b"content").unwrap();
+        }
+
+        // Imprinting would not record files this new, so entries are inserted directly.
+        let _ = fs::remove_file(&log);
+        let mut cache = Cache::open(&log).unwrap();
+        for path in &[&kept, &deleted, &modified] {
+            let (id, stamp) = identify(path).unwrap();
+            let entry = Entry {
+                path: fs::canonicalize(path).unwrap(),
+                stamp,
+                imprint: Imprint::from_memory(b"content").unwrap(),
+            };
+            cache.insert(id, entry).unwrap();
+        }
+        assert_eq!(Cache::open(&log).unwrap().len(), 3);
+
+        fs::remove_file(&deleted).unwrap();
+        fs::write(&modified, b"new content").unwrap();
+        cache.compact().unwrap();
+        assert_eq!(cache.len(), 1);
+
+        let reopened = Cache::open(&log).unwrap();
+        let (id, stamp) = identify(&kept).unwrap();
+        assert!(reopened.get(&id, &stamp).is_some());
+        assert_eq!(reopened.len(), 1);
+
+        for path in &[&log, &kept, &modified] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+}
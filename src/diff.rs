@@ -0,0 +1,249 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+};
+
+use crate::{Imprint, Manifest};
+
+/// The changes between two manifests of a tree.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Diff {
+    changes: Vec<Change>,
+}
+
+/// A change to a single file between two manifests.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Change {
+    /// A file that is new, with content not found anywhere in the old manifest.
+    Added { path: PathBuf },
+
+    /// A file that is gone, and whose content was not renamed to a new path.
+    Removed { path: PathBuf },
+
+    /// A file whose content changed in place.
+    Modified { path: PathBuf },
+
+    /// A file that was renamed or moved, keeping its content.
+    Renamed { from: PathBuf, to: PathBuf },
+
+    /// A new file with the content of a file in the old manifest, which may itself have since
+    /// been renamed or modified.
+    Copied { from: PathBuf, to: PathBuf },
+}
+
+impl Manifest {
+    /// Finds the changes that turn this manifest into `new`.
+    ///
+    /// Files are matched by imprint, so a file that appears at a new path with the content of a
+    /// removed file is reported as renamed, and one with the content of a file that remains is
+    /// reported as copied. When several removed files share an imprint, they are paired with the
+    /// new paths in sorted order. Files at the same path with a different imprint are reported
+    /// as modified, and are not matched to other paths.
+    ///
+    /// Empty files all share one imprint, so they are never matched to other paths: a new empty
+    /// file is reported as added, and a removed one as removed.
+    pub fn diff(&self, new: &Manifest) -> Diff {
+        // Paths are visited in sorted order, so each queue of removed files is sorted too.
+        let mut sources: HashMap<&Imprint, &Path> = HashMap::new();
+        let mut removed: HashMap<&Imprint, VecDeque<&Path>> = HashMap::new();
+        let mut removed_empty = Vec::new();
+        for (path, imprint) in self.iter() {
+            if imprint.is_empty() {
+                if new.get(path).is_none() {
+                    removed_empty.push(path);
+                }
+                continue;
+            }
+
+            sources.entry(imprint).or_insert(path);
+            if new.get(path).is_none() {
+                removed.entry(imprint).or_default().push_back(path);
+            }
+        }
+
+        let mut changes = Vec::new();
+        for (path, imprint) in new.iter() {
+            let change = match self.get(path) {
+                Some(old) if old == imprint => continue,
+                Some(_) => Change::Modified { path: path.into() },
+                None => match removed.get_mut(imprint).and_then(VecDeque::pop_front) {
+                    Some(from) => Change::Renamed {
+                        from: from.into(),
+                        to: path.into(),
+                    },
+                    None => match sources.get(imprint) {
+                        Some(from) => Change::Copied {
+                            from: from.into(),
+                            to: path.into(),
+                        },
+                        None => Change::Added { path: path.into() },
+                    },
+                },
+            };
+            changes.push(change);
+        }
+
+        changes.extend(
+            removed
+                .into_values()
+                .flatten()
+                .chain(removed_empty)
+                .map(|path| Change::Removed { path: path.into() }),
+        );
+        changes.sort_by(|a, b| a.path().cmp(b.path()));
+        Diff { changes }
+    }
+}
+
+impl Diff {
+    /// The changes, sorted by the path each change leaves behind.
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// True if the manifests describe the same files.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+}
+
+impl IntoIterator for Diff {
+    type Item = Change;
+    type IntoIter = std::vec::IntoIter<Change>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Diff {
+    type Item = &'a Change;
+    type IntoIter = std::slice::Iter<'a, Change>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.iter()
+    }
+}
+
+impl Change {
+    /// The path of the file after the change, or the path of a removed file.
+    pub fn path(&self) -> &Path {
+        match self {
+            Change::Added { path } | Change::Removed { path } | Change::Modified { path } => path,
+            Change::Renamed { to, .. } | Change::Copied { to, .. } => to,
+        }
+    }
+
+    /// The path of the file whose content was renamed or copied.
+    pub fn source(&self) -> Option<&Path> {
+        match self {
+            Change::Renamed { from, .. } | Change::Copied { from, .. } => Some(from),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(files: &[(&str, &[u8])]) -> Manifest {
+        files
+            .iter()
+            .map(|(path, content)| (*path, Imprint::from_memory(content).unwrap()))
+            .collect()
+    }
+
+    fn path(path: &str) -> PathBuf {
+        path.into()
+    }
+
+    #[test]
+    fn unchanged_manifests_have_no_changes() {
+        let old = manifest(&[("a", b"one"), ("b", b"two")]);
+        assert!(old.diff(&old.clone()).is_empty());
+    }
+
+    #[test]
+    fn changes_are_classified() {
+        let old = manifest(&[("kept", b"kept"), ("gone", b"gone"), ("moved", b"moved")]);
+        let new = manifest(&[
+            ("kept", b"changed"),
+            ("moved-to", b"moved"),
+            ("copy", b"kept"),
+            ("new", b"new"),
+        ]);
+
+        let expected = [
+            Change::Copied {
+                from: path("kept"),
+                to: path("copy"),
+            },
+            Change::Removed { path: path("gone") },
+            Change::Modified { path: path("kept") },
+            Change::Renamed {
+                from: path("moved"),
+                to: path("moved-to"),
+            },
+            Change::Added { path: path("new") },
+        ];
+        assert_eq!(old.diff(&new).changes(), expected);
+    }
+
+    #[test]
+    fn removed_files_sharing_an_imprint_pair_in_order() {
+        let old = manifest(&[("a", b"same"), ("b", b"same"), ("kept", b"other")]);
+        let new = manifest(&[
+            ("c", b"same"),
+            ("d", b"same"),
+            ("e", b"same"),
+            ("kept", b"other"),
+        ]);
+
+        let expected = [
+            Change::Renamed {
+                from: path("a"),
+                to: path("c"),
+            },
+            Change::Renamed {
+                from: path("b"),
+                to: path("d"),
+            },
+            Change::Copied {
+                from: path("a"),
+                to: path("e"),
+            },
+        ];
+        assert_eq!(old.diff(&new).changes(), expected);
+    }
+
+    #[test]
+    fn modified_paths_are_not_matched_elsewhere() {
+        let old = manifest(&[("a", b"one"), ("b", b"two")]);
+        let new = manifest(&[("a", b"two"), ("b", b"two")]);
+        assert_eq!(
+            old.diff(&new).changes(),
+            [Change::Modified { path: path("a") }]
+        );
+    }
+
+    #[test]
+    fn empty_files_are_not_matched() {
+        let old = manifest(&[("empty", b""), ("kept", b"")]);
+        let new = manifest(&[("kept", b""), ("new", b""), ("other", b"")]);
+        let expected = [
+            Change::Removed {
+                path: path("empty"),
+            },
+            Change::Added { path: path("new") },
+            Change::Added {
+                path: path("other"),
+            },
+        ];
+        assert_eq!(old.diff(&new).changes(), expected);
+    }
+}
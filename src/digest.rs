@@ -0,0 +1,303 @@
+use std::fmt::{self, Display};
+
+use blake3::KEY_LEN;
+
+use crate::DecodeError;
+
+/// The longest hash produced by any supported algorithm.
+const MAX_LEN: usize = 32;
+
+/// The algorithm used to hash samples.
+///
+/// BLAKE3 is always available. Other algorithms are enabled by cargo features of the same name:
+/// `sha2` for SHA-256 and `xxh3` for the 128-bit XXH3 hash. Imprints made with different
+/// algorithms never compare equal.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Algorithm {
+    #[default]
+    Blake3,
+
+    /// SHA-256, with HMAC-SHA-256 for keyed imprints.
+    #[cfg(feature = "sha2")]
+    Sha256,
+
+    /// XXH3 with 128-bit output.
+    ///
+    /// XXH3 is much faster than the cryptographic algorithms but offers no resistance to crafted
+    /// collisions, even when keyed. Use it only to group content that is not adversarial.
+    #[cfg(feature = "xxh3")]
+    Xxh3,
+}
+
+/// A hash of sampled content, made with the algorithm recorded in its imprint.
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+pub struct SampleHash {
+    bytes: [u8; MAX_LEN],
+    len: u8,
+}
+
+/// Separates sample hashes from hashes made for other purposes.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Domain {
+    /// The plain hash of the sample, as in legacy imprints.
+    Plain,
+
+    /// A hash in the named context.
+    Context(&'static str),
+
+    /// A hash keyed by a secret.
+    Keyed([u8; KEY_LEN]),
+}
+
+/// An incremental hash function.
+pub(crate) trait Digest {
+    type Output;
+
+    fn update(&mut self, bytes: &[u8]);
+
+    fn finalize(self) -> Self::Output;
+}
+
+/// Hashes a sample with any supported algorithm.
+#[derive(Clone)]
+pub(crate) enum Sampler {
+    Blake3(Box<blake3::Hasher>),
+    #[cfg(feature = "sha2")]
+    Sha256(Sha256),
+    #[cfg(feature = "xxh3")]
+    Xxh3(Box<xxhash_rust::xxh3::Xxh3>),
+}
+
+#[cfg(feature = "sha2")]
+#[derive(Clone)]
+pub(crate) enum Sha256 {
+    Plain(sha2::Sha256),
+    Keyed(hmac::Hmac<sha2::Sha256>),
+}
+
+impl Algorithm {
+    /// The length in bytes of the hashes made by this algorithm.
+    pub fn output_len(self) -> usize {
+        match self {
+            Algorithm::Blake3 => blake3::OUT_LEN,
+            #[cfg(feature = "sha2")]
+            Algorithm::Sha256 => 32,
+            #[cfg(feature = "xxh3")]
+            Algorithm::Xxh3 => 16,
+        }
+    }
+
+    /// The name of the algorithm, as written in the canonical text form.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Algorithm::Blake3 => "blake3",
+            #[cfg(feature = "sha2")]
+            Algorithm::Sha256 => "sha256",
+            #[cfg(feature = "xxh3")]
+            Algorithm::Xxh3 => "xxh3",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Result<Self, DecodeError> {
+        match name {
+            "blake3" => Ok(Algorithm::Blake3),
+            #[cfg(feature = "sha2")]
+            "sha256" => Ok(Algorithm::Sha256),
+            #[cfg(feature = "xxh3")]
+            "xxh3" => Ok(Algorithm::Xxh3),
+            _ => Err(DecodeError::UnsupportedAlgorithm),
+        }
+    }
+
+    /// The tag identifying the algorithm in the binary encoding.
+    pub(crate) fn tag(self) -> u8 {
+        match self {
+            Algorithm::Blake3 => 0,
+            #[cfg(feature = "sha2")]
+            Algorithm::Sha256 => 1,
+            #[cfg(feature = "xxh3")]
+            Algorithm::Xxh3 => 2,
+        }
+    }
+
+    pub(crate) fn from_tag(tag: u8) -> Result<Self, DecodeError> {
+        match tag {
+            0 => Ok(Algorithm::Blake3),
+            #[cfg(feature = "sha2")]
+            1 => Ok(Algorithm::Sha256),
+            #[cfg(feature = "xxh3")]
+            2 => Ok(Algorithm::Xxh3),
+            _ => Err(DecodeError::UnsupportedAlgorithm),
+        }
+    }
+}
+
+/// Decodes lowercase hex into `bytes`, which it must fill exactly.
+///
+/// Only the form written by this crate is accepted, so that each value has a single spelling.
+pub(crate) fn decode_hex(hex: &str, bytes: &mut [u8]) -> Option<()> {
+    fn digit(c: u8) -> Option<u8> {
+        match c {
+            b'0'..=b'9' => Some(c - b'0'),
+            b'a'..=b'f' => Some(c - b'a' + 10),
+            _ => None,
+        }
+    }
+
+    if hex.len() != bytes.len() * 2 {
+        return None;
+    }
+    for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = digit(pair[0])? << 4 | digit(pair[1])?;
+    }
+    Some(())
+}
+
+impl SampleHash {
+    fn new(bytes: &[u8]) -> Self {
+        let mut hash = SampleHash {
+            bytes: [0; MAX_LEN],
+            len: bytes.len() as u8,
+        };
+        hash.bytes[..bytes.len()].copy_from_slice(bytes);
+        hash
+    }
+
+    /// Parses a hex hash of the length made by `algorithm`.
+    pub(crate) fn from_hex(hex: &str, algorithm: Algorithm) -> Option<Self> {
+        let len = algorithm.output_len();
+        if hex.len() != len * 2 {
+            return None;
+        }
+
+        let mut bytes = [0; MAX_LEN];
+        decode_hex(hex, &mut bytes[..len])?;
+        Some(SampleHash::new(&bytes[..len]))
+    }
+
+    /// Reads a hash of the length made by `algorithm` from the start of `bytes`.
+    pub(crate) fn from_bytes(bytes: &[u8], algorithm: Algorithm) -> Option<Self> {
+        bytes.get(..algorithm.output_len()).map(SampleHash::new)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+}
+
+impl Display for SampleHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.as_bytes() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for SampleHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SampleHash({})", self)
+    }
+}
+
+impl Sampler {
+    pub(crate) fn new(algorithm: Algorithm, domain: Domain) -> Self {
+        match algorithm {
+            Algorithm::Blake3 => Sampler::Blake3(Box::new(match domain {
+                Domain::Plain => blake3::Hasher::new(),
+                Domain::Context(context) => blake3::Hasher::new_derive_key(context),
+                Domain::Keyed(key) => blake3::Hasher::new_keyed(&key),
+            })),
+            #[cfg(feature = "sha2")]
+            Algorithm::Sha256 => {
+                use hmac::Mac;
+                use sha2::Digest as _;
+
+                Sampler::Sha256(match domain {
+                    Domain::Plain => Sha256::Plain(sha2::Sha256::new()),
+                    // Contexts never contain a NUL byte, so the terminated context is an
+                    // unambiguous prefix.
+                    Domain::Context(context) => {
+                        Sha256::Plain(sha2::Sha256::new().chain_update(context).chain_update([0]))
+                    }
+                    Domain::Keyed(key) => Sha256::Keyed(
+                        hmac::Hmac::new_from_slice(&key).expect("HMAC accepts keys of any length"),
+                    ),
+                })
+            }
+            #[cfg(feature = "xxh3")]
+            Algorithm::Xxh3 => {
+                use std::convert::TryInto;
+                use xxhash_rust::xxh3::{xxh3_64, Xxh3};
+
+                let seed = match domain {
+                    Domain::Plain => 0,
+                    Domain::Context(context) => xxh3_64(context.as_bytes()),
+                    Domain::Keyed(key) => u64::from_le_bytes(key[..8].try_into().unwrap()),
+                };
+                Sampler::Xxh3(Box::new(Xxh3::with_seed(seed)))
+            }
+        }
+    }
+}
+
+impl Digest for Sampler {
+    type Output = SampleHash;
+
+    fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Sampler::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+            #[cfg(feature = "sha2")]
+            Sampler::Sha256(Sha256::Plain(hasher)) => sha2::Digest::update(hasher, bytes),
+            #[cfg(feature = "sha2")]
+            Sampler::Sha256(Sha256::Keyed(mac)) => hmac::Mac::update(mac, bytes),
+            #[cfg(feature = "xxh3")]
+            Sampler::Xxh3(hasher) => hasher.update(bytes),
+        }
+    }
+
+    fn finalize(self) -> SampleHash {
+        match self {
+            Sampler::Blake3(hasher) => SampleHash::new(hasher.finalize().as_bytes()),
+            #[cfg(feature = "sha2")]
+            Sampler::Sha256(Sha256::Plain(hasher)) => {
+                SampleHash::new(&sha2::Digest::finalize(hasher))
+            }
+            #[cfg(feature = "sha2")]
+            Sampler::Sha256(Sha256::Keyed(mac)) => {
+                SampleHash::new(&hmac::Mac::finalize(mac).into_bytes())
+            }
+            #[cfg(feature = "xxh3")]
+            Sampler::Xxh3(hasher) => SampleHash::new(&hasher.digest128().to_be_bytes()),
+        }
+    }
+}
+
+impl fmt::Debug for Sampler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let algorithm = match self {
+            Sampler::Blake3(_) => Algorithm::Blake3,
+            #[cfg(feature = "sha2")]
+            Sampler::Sha256(_) => Algorithm::Sha256,
+            #[cfg(feature = "xxh3")]
+            Sampler::Xxh3(_) => Algorithm::Xxh3,
+        };
+        f.debug_tuple("Sampler").field(&algorithm).finish()
+    }
+}
+
+impl Digest for blake3::Hasher {
+    type Output = blake3::Hash;
+
+    fn update(&mut self, bytes: &[u8]) {
+        blake3::Hasher::update(self, bytes);
+    }
+
+    fn finalize(self) -> blake3::Hash {
+        blake3::Hasher::finalize(&self)
+    }
+}
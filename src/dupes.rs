@@ -0,0 +1,231 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::{self, Metadata},
+    path::{Path, PathBuf},
+};
+
+use crate::{platform::FileId, Imprint, ImprintError};
+
+/// Finds groups of files with identical content.
+///
+/// Files are first grouped by length, and only files sharing a length are imprinted. Files
+/// sharing an imprint are reported as duplicates, after hashing their entire content if
+/// confirmation is enabled.
+#[derive(Clone, Debug, Default)]
+pub struct DuplicateFinder {
+    confirm: bool,
+}
+
+/// The duplicates found among a set of files.
+#[derive(Debug, Default)]
+pub struct Duplicates {
+    groups: Vec<DuplicateGroup>,
+    errors: Vec<ImprintError>,
+}
+
+/// A group of files believed to have identical content.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct DuplicateGroup {
+    len: u64,
+    paths: Vec<PathBuf>,
+    files: usize,
+}
+
+impl DuplicateFinder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether files sharing an imprint are confirmed by hashing their entire content.
+    ///
+    /// Without confirmation, files are reported as duplicates on the strength of their imprints
+    /// alone. By default, duplicates are not confirmed.
+    pub fn confirm(mut self, confirm: bool) -> Self {
+        self.confirm = confirm;
+        self
+    }
+
+    /// Finds the duplicates among the given files.
+    ///
+    /// A file that cannot be read is reported as an error and left out of every group, without
+    /// aborting the search. Paths given more than once are considered only once.
+    ///
+    /// On Unix, hard links to the same file are imprinted once and grouped together. They are
+    /// reported only alongside a distinct file with the same content, and sharing their storage,
+    /// count only once towards the bytes that could be reclaimed.
+    pub fn find<P: Into<PathBuf>>(&self, paths: impl IntoIterator<Item = P>) -> Duplicates {
+        let mut errors = Vec::new();
+        let mut seen = HashSet::new();
+        let mut by_len: BTreeMap<u64, HashMap<FileId, Vec<PathBuf>>> = BTreeMap::new();
+
+        for path in paths {
+            let path = path.into();
+            if !seen.insert(path.clone()) {
+                continue;
+            }
+
+            let meta = fs::metadata(&path)
+                .map_err(|e| ImprintError::from(e).with_path(&path))
+                .and_then(|meta| ImprintError::check_file(&path, &meta).map(|_| meta));
+            match meta {
+                Ok(meta) => by_len
+                    .entry(meta.len())
+                    .or_default()
+                    .entry(file_id(&path, &meta))
+                    .or_default()
+                    .push(path),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        let mut groups = Vec::new();
+        for candidates in by_len.into_values().filter(|files| files.len() > 1) {
+            let mut by_imprint: HashMap<Imprint, Vec<Vec<PathBuf>>> = HashMap::new();
+            for links in candidates.into_values() {
+                match Imprint::new(&links[0]) {
+                    Ok(imprint) => by_imprint.entry(imprint).or_default().push(links),
+                    Err(e) => errors.push(e),
+                }
+            }
+
+            for (imprint, files) in by_imprint.into_iter().filter(|(_, files)| files.len() > 1) {
+                if self.confirm {
+                    groups.extend(confirm(&imprint, files, &mut errors));
+                } else {
+                    groups.push(DuplicateGroup::new(imprint.len(), files));
+                }
+            }
+        }
+
+        groups.sort_by(|a, b| b.len.cmp(&a.len).then_with(|| a.paths.cmp(&b.paths)));
+        Duplicates { groups, errors }
+    }
+}
+
+/// Splits files sharing an imprint into groups sharing a full hash.
+///
+/// Each file is given as the paths that link to it.
+fn confirm(
+    imprint: &Imprint,
+    files: Vec<Vec<PathBuf>>,
+    errors: &mut Vec<ImprintError>,
+) -> impl Iterator<Item = DuplicateGroup> {
+    let mut by_hash = HashMap::new();
+    for links in files {
+        match imprint.clone().upgrade(&links[0]) {
+            Ok(full) => by_hash
+                .entry(full.hash())
+                .or_insert_with(Vec::new)
+                .push(links),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    let len = imprint.len();
+    by_hash
+        .into_values()
+        .filter(|files| files.len() > 1)
+        .map(move |files| DuplicateGroup::new(len, files))
+}
+
+#[cfg(unix)]
+fn file_id(_path: &Path, meta: &Metadata) -> FileId {
+    use std::os::unix::fs::MetadataExt;
+    (meta.dev(), meta.ino())
+}
+
+/// Without inodes, every path is taken to be a distinct file.
+#[cfg(not(unix))]
+fn file_id(path: &Path, _meta: &Metadata) -> FileId {
+    path.to_path_buf()
+}
+
+impl Duplicates {
+    /// The groups of duplicate files, largest files first.
+    pub fn groups(&self) -> &[DuplicateGroup] {
+        &self.groups
+    }
+
+    /// The errors encountered for files that could not be examined.
+    pub fn errors(&self) -> &[ImprintError] {
+        &self.errors
+    }
+
+    /// The total number of bytes that would be freed by keeping one file from each group.
+    pub fn reclaimable(&self) -> u64 {
+        self.groups.iter().map(DuplicateGroup::reclaimable).sum()
+    }
+
+    pub fn into_parts(self) -> (Vec<DuplicateGroup>, Vec<ImprintError>) {
+        (self.groups, self.errors)
+    }
+}
+
+impl DuplicateGroup {
+    /// Makes a group from the paths that link to each of its files.
+    fn new(len: u64, files: Vec<Vec<PathBuf>>) -> Self {
+        let count = files.len();
+        let mut paths: Vec<PathBuf> = files.into_iter().flatten().collect();
+        paths.sort();
+        DuplicateGroup {
+            len,
+            paths,
+            files: count,
+        }
+    }
+
+    /// The length, in bytes, of each file in the group.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// True if the files in the group are empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The paths of the files in the group, in sorted order.
+    ///
+    /// Hard links to the same file each appear, so there may be more paths than files.
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// The number of distinct files in the group, counting hard links to a file once.
+    pub fn files(&self) -> usize {
+        self.files
+    }
+
+    /// The number of bytes that would be freed by keeping only one file from the group.
+    pub fn reclaimable(&self) -> u64 {
+        self.len * (self.files as u64 - 1)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hard_links_are_not_duplicates() {
+        let base = std::env::temp_dir().join(format!("imprint-links-{}", std::process::id()));
+        fs::create_dir_all(&base).unwrap();
+        let (original, link, copy) = (base.join("original"), base.join("link"), base.join("copy"));
+        fs::write(&original, b"content").unwrap();
+        fs::hard_link(&original, &link).unwrap();
+
+        let finder = DuplicateFinder::new().confirm(true);
+        let duplicates = finder.find([&original, &link]);
+        assert!(duplicates.groups().is_empty());
+        assert_eq!(duplicates.reclaimable(), 0);
+
+        fs::write(&copy, b"content").unwrap();
+        let duplicates = finder.find([&original, &link, &copy]);
+        assert_eq!(duplicates.groups().len(), 1);
+        assert_eq!(duplicates.groups()[0].paths().len(), 3);
+        assert_eq!(duplicates.groups()[0].files(), 2);
+        assert_eq!(duplicates.reclaimable(), 7);
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,339 @@
+use std::convert::TryInto;
+
+use crate::{Algorithm, DecodeError, Imprint, Interior, KeyId, SampleHash, Sampling, Version};
+
+/// The current version of the binary encoding.
+const VERSION: u8 = 4;
+
+const INTERIOR_NONE: u8 = 0;
+const INTERIOR_EVEN: u8 = 1;
+const INTERIOR_DERIVED: u8 = 2;
+
+const HAS_TAIL: u8 = 1;
+
+const HASH_LEGACY: u8 = 0;
+const HASH_V1: u8 = 1;
+
+const UNKEYED: u8 = 0;
+const KEYED: u8 = 1;
+
+impl Imprint {
+    /// Encodes the imprint in a compact, versioned binary form.
+    ///
+    /// Every field is encoded, so `Imprint::from_bytes` always returns an equal imprint.
+    /// Encodings written by this release remain readable by future releases.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let hash_len = self.algorithm.output_len();
+        let mut buf = Vec::with_capacity(64 + hash_len * (2 + self.interior.len()));
+        buf.push(VERSION);
+        buf.push(match self.version {
+            Version::Legacy => HASH_LEGACY,
+            Version::V1 => HASH_V1,
+        });
+        buf.push(self.algorithm.tag());
+        match &self.key_id {
+            None => buf.push(UNKEYED),
+            Some(key_id) => {
+                buf.push(KEYED);
+                buf.extend_from_slice(key_id.as_bytes());
+            }
+        }
+        buf.extend_from_slice(&self.len.to_le_bytes());
+        buf.extend_from_slice(&self.sampling.head.to_le_bytes());
+        buf.extend_from_slice(&self.sampling.tail.to_le_bytes());
+
+        let (tag, params) = match self.sampling.interior {
+            Interior::None => (INTERIOR_NONE, None),
+            Interior::Even { count, size } => (INTERIOR_EVEN, Some((count, size))),
+            Interior::Derived { count, size } => (INTERIOR_DERIVED, Some((count, size))),
+        };
+        buf.push(tag);
+        if let Some((count, size)) = params {
+            buf.extend_from_slice(&count.to_le_bytes());
+            buf.extend_from_slice(&size.to_le_bytes());
+        }
+
+        buf.push(if self.tail.is_some() { HAS_TAIL } else { 0 });
+        buf.extend_from_slice(self.head.as_bytes());
+        if let Some(tail) = &self.tail {
+            buf.extend_from_slice(tail.as_bytes());
+        }
+
+        buf.extend_from_slice(&(self.interior.len() as u32).to_le_bytes());
+        for hash in self.interior.iter() {
+            buf.extend_from_slice(hash.as_bytes());
+        }
+
+        buf
+    }
+
+    /// Decodes an imprint from the form produced by `Imprint::to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut decoder = Decoder {
+            bytes,
+            algorithm: Algorithm::Blake3,
+        };
+        let imprint = match decoder.u8()? {
+            // Version 1 predates versioned hashing, so its samples were hashed with plain BLAKE3.
+            1 => decoder.body(Version::Legacy, None)?,
+            // Version 2 predates keyed imprints.
+            2 => {
+                let version = decoder.version()?;
+                decoder.body(version, None)?
+            }
+            // Version 3 predates pluggable algorithms, so its samples were hashed with BLAKE3.
+            3 => {
+                let version = decoder.version()?;
+                let key_id = decoder.key_id()?;
+                decoder.body(version, key_id)?
+            }
+            4 => {
+                let version = decoder.version()?;
+                decoder.algorithm = Algorithm::from_tag(decoder.u8()?)?;
+                let key_id = decoder.key_id()?;
+                decoder.body(version, key_id)?
+            }
+            version => return Err(DecodeError::UnsupportedVersion(version)),
+        };
+
+        if !decoder.bytes.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        imprint.validate()?;
+        Ok(imprint)
+    }
+
+    /// Checks that the sampled hashes are consistent with the length and sampling.
+    pub(crate) fn validate(&self) -> Result<(), DecodeError> {
+        let has_tail = self.sampling.tail_len(self.len) > 0;
+        let interior = self.sampling.interior_count(self.len);
+        if self.tail.is_some() != has_tail || self.interior.len() != interior {
+            return Err(DecodeError::Inconsistent);
+        }
+        Ok(())
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    algorithm: Algorithm,
+}
+
+impl<'a> Decoder<'a> {
+    fn version(&mut self) -> Result<Version, DecodeError> {
+        match self.u8()? {
+            HASH_LEGACY => Ok(Version::Legacy),
+            HASH_V1 => Ok(Version::V1),
+            tag => Err(DecodeError::InvalidTag(tag)),
+        }
+    }
+
+    fn key_id(&mut self) -> Result<Option<KeyId>, DecodeError> {
+        match self.u8()? {
+            UNKEYED => Ok(None),
+            KEYED => Ok(Some(KeyId(self.take(16)?.try_into().unwrap()))),
+            tag => Err(DecodeError::InvalidTag(tag)),
+        }
+    }
+
+    fn body(&mut self, version: Version, key_id: Option<KeyId>) -> Result<Imprint, DecodeError> {
+        let len = self.u64()?;
+        let head = self.u64()?;
+        let tail = self.u64()?;
+
+        let interior = match self.u8()? {
+            INTERIOR_NONE => Interior::None,
+            INTERIOR_EVEN => Interior::Even {
+                count: self.u32()?,
+                size: self.u64()?,
+            },
+            INTERIOR_DERIVED => Interior::Derived {
+                count: self.u32()?,
+                size: self.u64()?,
+            },
+            tag => return Err(DecodeError::InvalidTag(tag)),
+        };
+
+        let flags = self.u8()?;
+        if flags & !HAS_TAIL != 0 {
+            return Err(DecodeError::InvalidTag(flags));
+        }
+
+        let head_hash = self.hash()?;
+        let tail_hash = if flags & HAS_TAIL != 0 {
+            Some(self.hash()?)
+        } else {
+            None
+        };
+
+        let count = self.u32()?;
+        if count as usize > self.bytes.len() / self.algorithm.output_len() {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+
+        Ok(Imprint {
+            len,
+            sampling: Sampling {
+                head,
+                tail,
+                interior,
+            },
+            version,
+            algorithm: self.algorithm,
+            key_id,
+            head: head_hash,
+            tail: tail_hash,
+            interior: (0..count).map(|_| self.hash()).collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], DecodeError> {
+        if self.bytes.len() < n {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn hash(&mut self) -> Result<SampleHash, DecodeError> {
+        let bytes = self.take(self.algorithm.output_len())?;
+        Ok(SampleHash::from_bytes(bytes, self.algorithm).unwrap())
+    }
+}
+
+#[cfg(feature = "encoding")]
+impl Imprint {
+    /// Encodes the binary form in lowercase, unpadded base32, suitable for file names on
+    /// case-insensitive filesystems.
+    pub fn to_base32(&self) -> String {
+        data_encoding::BASE32_DNSSEC.encode(&self.to_bytes())
+    }
+
+    /// Decodes an imprint from the form produced by `Imprint::to_base32`.
+    pub fn from_base32(s: &str) -> Result<Self, DecodeError> {
+        let bytes = data_encoding::BASE32_DNSSEC
+            .decode(s.as_bytes())
+            .map_err(|_| DecodeError::Malformed)?;
+        Imprint::from_bytes(&bytes)
+    }
+
+    /// Encodes the binary form in unpadded, URL-safe base64.
+    pub fn to_base64(&self) -> String {
+        data_encoding::BASE64URL_NOPAD.encode(&self.to_bytes())
+    }
+
+    /// Decodes an imprint from the form produced by `Imprint::to_base64`.
+    pub fn from_base64(s: &str) -> Result<Self, DecodeError> {
+        let bytes = data_encoding::BASE64URL_NOPAD
+            .decode(s.as_bytes())
+            .map_err(|_| DecodeError::Malformed)?;
+        Imprint::from_bytes(&bytes)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::Imprinter;
+
+    use super::*;
+
+    /// The content imprinted by the fixtures, which were written by a release using version 1
+    /// of the binary encoding and the `imp1` text form.
+    pub(crate) fn fixture_content() -> Vec<u8> {
+        (0..0x50000u32).map(|i| (i * 13 + i / 257) as u8).collect()
+    }
+
+    /// The imprint of the fixture content as the fixtures describe it.
+    pub(crate) fn fixture_imprint() -> Imprint {
+        Imprinter::new()
+            .version(Version::Legacy)
+            .interior(Interior::Even {
+                count: 2,
+                size: 4096,
+            })
+            .imprint_memory(&fixture_content())
+            .unwrap()
+    }
+
+    /// Imprints covering each field of the encodings.
+    pub(crate) fn imprints() -> Vec<Imprint> {
+        let content = fixture_content();
+        let mut imprinters = vec![
+            Imprinter::new(),
+            Imprinter::new().version(Version::Legacy),
+            Imprinter::new().head_size(1000).tail_size(0),
+            Imprinter::new().interior(Interior::Even {
+                count: 3,
+                size: 100,
+            }),
+            Imprinter::new().interior(Interior::Derived {
+                count: 2,
+                size: 5000,
+            }),
+            Imprinter::new().key([7; 32]),
+        ];
+        #[cfg(feature = "sha2")]
+        imprinters.push(Imprinter::new().algorithm(Algorithm::Sha256));
+        #[cfg(feature = "xxh3")]
+        imprinters.push(Imprinter::new().algorithm(Algorithm::Xxh3));
+
+        let mut imprints = Vec::new();
+        for imprinter in &mut imprinters {
+            for len in [0, 10, 0x20000, 0x20001, content.len()] {
+                imprints.push(imprinter.imprint_memory(&content[..len]).unwrap());
+            }
+        }
+        imprints
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        for imprint in imprints() {
+            assert_eq!(Imprint::from_bytes(&imprint.to_bytes()), Ok(imprint));
+        }
+    }
+
+    #[test]
+    fn version_1_bytes_decode() {
+        let bytes = include_bytes!("../tests/fixtures/imprint-v1.bin");
+        let imprint = Imprint::from_bytes(bytes).unwrap();
+        assert_eq!(imprint, fixture_imprint());
+        assert_eq!(Imprint::from_bytes(&imprint.to_bytes()), Ok(imprint));
+    }
+
+    #[test]
+    fn damaged_bytes_are_rejected() {
+        let bytes = fixture_imprint().to_bytes();
+        for len in 0..bytes.len() {
+            assert!(Imprint::from_bytes(&bytes[..len]).is_err());
+        }
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            Imprint::from_bytes(&trailing),
+            Err(DecodeError::TrailingBytes)
+        );
+
+        let mut unsupported = bytes;
+        unsupported[0] = VERSION + 1;
+        assert_eq!(
+            Imprint::from_bytes(&unsupported),
+            Err(DecodeError::UnsupportedVersion(VERSION + 1))
+        );
+    }
+}
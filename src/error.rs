@@ -0,0 +1,322 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    fs::Metadata,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+/// An error encountered while imprinting.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ImprintError {
+    /// The path does not refer to a regular file.
+    NotAFile { path: PathBuf, kind: FileKind },
+
+    /// The content could not be opened or read for lack of permission.
+    PermissionDenied {
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+
+    /// The length of the content changed while it was being imprinted.
+    SizeChanged {
+        path: Option<PathBuf>,
+        expected: u64,
+        actual: u64,
+    },
+
+    /// The content ended before its expected length.
+    Truncated {
+        path: Option<PathBuf>,
+        expected: u64,
+    },
+
+    /// The file was modified while it was being imprinted.
+    Unstable { path: Option<PathBuf> },
+
+    /// The content no longer matches the imprint it was expected to have.
+    Mismatch { path: Option<PathBuf> },
+
+    /// Interior sampling of a stream was requested without the length of the content.
+    LengthRequired,
+
+    /// A keyed imprint was used without its key.
+    KeyRequired,
+
+    /// Any other I/O error.
+    Io {
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+}
+
+/// An error encountered while decoding an imprint.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum DecodeError {
+    /// The input ended before the imprint was complete.
+    UnexpectedEnd,
+
+    /// The input was written by an unknown version of the encoding.
+    UnsupportedVersion(u8),
+
+    /// The input contains an unrecognized tag or flag.
+    InvalidTag(u8),
+
+    /// The imprint was made with a hash algorithm that is unknown or not enabled in this build.
+    UnsupportedAlgorithm,
+
+    /// The text is not a valid textual form of an imprint.
+    Malformed,
+
+    /// The decoded fields do not describe a valid imprint.
+    Inconsistent,
+
+    /// The input continues past the end of the imprint.
+    TrailingBytes,
+}
+
+/// The kind of a filesystem entry that is not a regular file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum FileKind {
+    Directory,
+    Symlink,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+    Unknown,
+}
+
+impl ImprintError {
+    /// The path of the offending file, if known.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            ImprintError::NotAFile { path, .. } => Some(path),
+            ImprintError::PermissionDenied { path, .. }
+            | ImprintError::SizeChanged { path, .. }
+            | ImprintError::Truncated { path, .. }
+            | ImprintError::Unstable { path }
+            | ImprintError::Mismatch { path }
+            | ImprintError::Io { path, .. } => path.as_deref(),
+            ImprintError::LengthRequired | ImprintError::KeyRequired => None,
+        }
+    }
+
+    /// Returns an error if the metadata does not describe a regular file.
+    pub(crate) fn check_file(path: &Path, meta: &Metadata) -> Result<(), Self> {
+        if meta.is_file() {
+            return Ok(());
+        }
+
+        Err(ImprintError::NotAFile {
+            path: path.into(),
+            kind: FileKind::of(meta),
+        })
+    }
+
+    /// Classifies an error encountered while reading `len` bytes of content.
+    pub(crate) fn reading(source: io::Error, len: u64) -> Self {
+        match source.kind() {
+            ErrorKind::UnexpectedEof => ImprintError::Truncated {
+                path: None,
+                expected: len,
+            },
+            _ => source.into(),
+        }
+    }
+
+    /// True if the error suggests the file was changing while it was read.
+    pub(crate) fn is_unstable(&self) -> bool {
+        matches!(
+            self,
+            ImprintError::SizeChanged { .. }
+                | ImprintError::Truncated { .. }
+                | ImprintError::Unstable { .. }
+        )
+    }
+
+    /// Attaches a path to the error, unless it already has one.
+    pub(crate) fn with_path(mut self, with: &Path) -> Self {
+        match &mut self {
+            ImprintError::PermissionDenied { path, .. }
+            | ImprintError::SizeChanged { path, .. }
+            | ImprintError::Truncated { path, .. }
+            | ImprintError::Unstable { path }
+            | ImprintError::Mismatch { path }
+            | ImprintError::Io { path, .. } => {
+                path.get_or_insert_with(|| with.into());
+            }
+            ImprintError::NotAFile { .. }
+            | ImprintError::LengthRequired
+            | ImprintError::KeyRequired => (),
+        }
+        self
+    }
+}
+
+impl FileKind {
+    #[cfg(unix)]
+    fn of(meta: &Metadata) -> Self {
+        use std::os::unix::fs::FileTypeExt;
+
+        let file_type = meta.file_type();
+        if file_type.is_dir() {
+            FileKind::Directory
+        } else if file_type.is_symlink() {
+            FileKind::Symlink
+        } else if file_type.is_fifo() {
+            FileKind::Fifo
+        } else if file_type.is_socket() {
+            FileKind::Socket
+        } else if file_type.is_block_device() {
+            FileKind::BlockDevice
+        } else if file_type.is_char_device() {
+            FileKind::CharDevice
+        } else {
+            FileKind::Unknown
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn of(meta: &Metadata) -> Self {
+        let file_type = meta.file_type();
+        if file_type.is_dir() {
+            FileKind::Directory
+        } else if file_type.is_symlink() {
+            FileKind::Symlink
+        } else {
+            FileKind::Unknown
+        }
+    }
+}
+
+impl Display for ImprintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImprintError::NotAFile { path, kind } => {
+                write!(f, "{}: expected a file, found {}", path.display(), kind)
+            }
+            ImprintError::PermissionDenied { path, source } => {
+                write_path(f, path)?;
+                write!(f, "permission denied: {}", source)
+            }
+            ImprintError::SizeChanged {
+                path,
+                expected,
+                actual,
+            } => {
+                write_path(f, path)?;
+                write!(
+                    f,
+                    "length changed from {} to {} while imprinting",
+                    expected, actual
+                )
+            }
+            ImprintError::Truncated { path, expected } => {
+                write_path(f, path)?;
+                write!(
+                    f,
+                    "content ended before its expected length of {}",
+                    expected
+                )
+            }
+            ImprintError::Unstable { path } => {
+                write_path(f, path)?;
+                f.write_str("file was modified while imprinting")
+            }
+            ImprintError::Mismatch { path } => {
+                write_path(f, path)?;
+                f.write_str("content does not match its imprint")
+            }
+            ImprintError::LengthRequired => {
+                f.write_str("interior sampling requires the length of the content")
+            }
+            ImprintError::KeyRequired => f.write_str("keyed imprint requires its key"),
+            ImprintError::Io { path, source } => {
+                write_path(f, path)?;
+                source.fmt(f)
+            }
+        }
+    }
+}
+
+fn write_path(f: &mut fmt::Formatter<'_>, path: &Option<PathBuf>) -> fmt::Result {
+    match path {
+        Some(path) => write!(f, "{}: ", path.display()),
+        None => Ok(()),
+    }
+}
+
+impl Display for FileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FileKind::Directory => "a directory",
+            FileKind::Symlink => "a symbolic link",
+            FileKind::Fifo => "a FIFO",
+            FileKind::Socket => "a socket",
+            FileKind::BlockDevice => "a block device",
+            FileKind::CharDevice => "a character device",
+            FileKind::Unknown => "an unknown file type",
+        })
+    }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEnd => f.write_str("unexpected end of encoded imprint"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported imprint encoding version {}", version)
+            }
+            DecodeError::InvalidTag(tag) => {
+                write!(f, "invalid tag {:#04x} in encoded imprint", tag)
+            }
+            DecodeError::UnsupportedAlgorithm => f.write_str("unsupported hash algorithm"),
+            DecodeError::Malformed => f.write_str("malformed imprint text"),
+            DecodeError::Inconsistent => f.write_str("encoded imprint is inconsistent"),
+            DecodeError::TrailingBytes => f.write_str("trailing bytes after encoded imprint"),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+impl Error for ImprintError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ImprintError::PermissionDenied { source, .. } | ImprintError::Io { source, .. } => {
+                Some(source)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ImprintError {
+    fn from(source: io::Error) -> Self {
+        match source.kind() {
+            ErrorKind::PermissionDenied => ImprintError::PermissionDenied { path: None, source },
+            _ => ImprintError::Io { path: None, source },
+        }
+    }
+}
+
+impl From<ImprintError> for io::Error {
+    fn from(error: ImprintError) -> Self {
+        let kind = match &error {
+            ImprintError::NotAFile { .. }
+            | ImprintError::LengthRequired
+            | ImprintError::KeyRequired => ErrorKind::InvalidInput,
+            ImprintError::PermissionDenied { source, .. } | ImprintError::Io { source, .. } => {
+                source.kind()
+            }
+            ImprintError::SizeChanged { .. }
+            | ImprintError::Unstable { .. }
+            | ImprintError::Mismatch { .. } => ErrorKind::InvalidData,
+            ImprintError::Truncated { .. } => ErrorKind::UnexpectedEof,
+        };
+        io::Error::new(kind, error)
+    }
+}
@@ -0,0 +1,150 @@
+use std::path::Path;
+
+use blake3::Hash;
+
+use crate::{
+    hashing::{Key, Scheme},
+    Imprint, ImprintError, Imprinter, ProgressiveImprint,
+};
+
+/// An imprint together with the BLAKE3 hash of the entire content.
+///
+/// Unlike an imprint, equal full imprints are proof of identical content.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct FullImprint {
+    imprint: Imprint,
+    hash: Hash,
+}
+
+impl FullImprint {
+    /// Imprints a file and hashes its entire content.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, ImprintError> {
+        let mut progressive = ProgressiveImprint::open(path)?;
+        Ok(FullImprint {
+            imprint: progressive.imprint()?,
+            hash: progressive.full()?,
+        })
+    }
+
+    pub fn imprint(&self) -> &Imprint {
+        &self.imprint
+    }
+
+    pub fn hash(&self) -> Hash {
+        self.hash
+    }
+}
+
+impl Imprint {
+    /// Upgrades this imprint to a full imprint by hashing the entire file at `path`.
+    ///
+    /// Fails if the file no longer matches this imprint. Keyed imprints must be upgraded with
+    /// `Imprinter::upgrade`, using an imprinter with the same key.
+    pub fn upgrade(self, path: impl AsRef<Path>) -> Result<FullImprint, ImprintError> {
+        if self.key_id.is_some() {
+            return Err(ImprintError::KeyRequired);
+        }
+
+        let scheme = self.scheme(None);
+        upgrade(self, path.as_ref(), scheme)
+    }
+
+    /// Re-imprints the file at `path` the way this imprint was made, and returns true if the
+    /// two imprints are equal.
+    ///
+    /// Keyed imprints must be verified with `Imprinter::verify`, using an imprinter with the same
+    /// key.
+    pub fn verify(&self, path: impl AsRef<Path>) -> Result<bool, ImprintError> {
+        if self.key_id.is_some() {
+            return Err(ImprintError::KeyRequired);
+        }
+        verify(self, path.as_ref(), self.scheme(None))
+    }
+
+    /// The scheme this imprint was made with, given its key.
+    fn scheme(&self, key: Option<Key>) -> Scheme {
+        Scheme {
+            version: self.version,
+            algorithm: self.algorithm,
+            key,
+        }
+    }
+}
+
+impl Imprinter {
+    /// Upgrades an imprint made by this imprinter to a full imprint by hashing the entire file
+    /// at `path`.
+    ///
+    /// Fails if the file no longer matches the imprint.
+    pub fn upgrade(
+        &self,
+        imprint: Imprint,
+        path: impl AsRef<Path>,
+    ) -> Result<FullImprint, ImprintError> {
+        let scheme = imprint.scheme(self.scheme().key);
+        upgrade(imprint, path.as_ref(), scheme)
+    }
+
+    /// Re-imprints the file at `path` the way an imprint made by this imprinter was made, and
+    /// returns true if the two imprints are equal.
+    pub fn verify(&self, imprint: &Imprint, path: impl AsRef<Path>) -> Result<bool, ImprintError> {
+        verify(imprint, path.as_ref(), imprint.scheme(self.scheme().key))
+    }
+}
+
+fn verify(imprint: &Imprint, path: &Path, scheme: Scheme) -> Result<bool, ImprintError> {
+    let mut progressive = ProgressiveImprint::open_with(path, imprint.sampling, scheme)?;
+    Ok(progressive.len() == imprint.len && progressive.imprint()? == *imprint)
+}
+
+fn upgrade(imprint: Imprint, path: &Path, scheme: Scheme) -> Result<FullImprint, ImprintError> {
+    let mut progressive = ProgressiveImprint::open_with(path, imprint.sampling, scheme)?;
+    if progressive.imprint()? != imprint {
+        return Err(ImprintError::Mismatch {
+            path: Some(path.into()),
+        });
+    }
+
+    Ok(FullImprint {
+        hash: progressive.full()?,
+        imprint,
+    })
+}
+
+/// Returns true only if two files have identical content.
+///
+/// Imprints are used to rule out differing files cheaply; files whose imprints match are
+/// confirmed by hashing their entire content. If either file changes before it has been hashed,
+/// the comparison fails with `ImprintError::SizeChanged` or `ImprintError::Unstable` rather
+/// than reporting content that may no longer be there.
+pub fn verify_same_content(a: impl AsRef<Path>, b: impl AsRef<Path>) -> Result<bool, ImprintError> {
+    let mut a = ProgressiveImprint::open(a)?;
+    let mut b = ProgressiveImprint::open(b)?;
+    Ok(a.compare(&mut b)?.equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn same_content_is_verified_by_full_hash() {
+        let base = std::env::temp_dir().join(format!("imprint-same-{}", std::process::id()));
+        fs::create_dir_all(&base).unwrap();
+        let (a, b, c) = (base.join("a"), base.join("b"), base.join("c"));
+        let content = vec![1; 0x50000];
+        let mut changed = content.clone();
+        changed[0x28000] = 2;
+        fs::write(&a, &content).unwrap();
+        fs::write(&b, &content).unwrap();
+        fs::write(&c, &changed).unwrap();
+
+        assert!(verify_same_content(&a, &b).unwrap());
+        assert!(!verify_same_content(&a, &c).unwrap());
+        assert_eq!(FullImprint::new(&a).unwrap().hash(), blake3::hash(&content));
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,153 @@
+use std::fmt::{self, Display};
+
+use blake3::{Hasher, KEY_LEN};
+
+use crate::{
+    digest::{decode_hex, Domain, Sampler},
+    Algorithm,
+};
+
+/// Version of the scheme used to hash samples.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Version {
+    /// Every sample is hashed with plain BLAKE3, as in releases before domain separation.
+    ///
+    /// Legacy head hashes of content no longer than the head sample equal the ordinary BLAKE3
+    /// hash of that content, and head and tail hashes of identical bytes are equal. This version
+    /// exists so that stored legacy imprints remain comparable during migration.
+    Legacy,
+
+    /// Each sample is hashed in its own BLAKE3 key derivation context, separated by role and
+    /// by version.
+    #[default]
+    V1,
+}
+
+/// Identifies the secret key used to make a keyed imprint, without revealing the key.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct KeyId(pub(crate) [u8; 16]);
+
+/// The role a sample plays within an imprint.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub(crate) enum Role {
+    Head,
+    Tail,
+    Interior,
+}
+
+/// A secret key for keyed imprints.
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+pub(crate) struct Key(pub(crate) [u8; KEY_LEN]);
+
+/// How samples are hashed.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub(crate) struct Scheme {
+    pub(crate) version: Version,
+    pub(crate) algorithm: Algorithm,
+    pub(crate) key: Option<Key>,
+}
+
+impl Scheme {
+    /// Returns a hasher for a sample playing the given role.
+    pub(crate) fn hasher(&self, role: Role) -> Sampler {
+        let domain = match (self.version, &self.key) {
+            (Version::Legacy, None) => Domain::Plain,
+            (Version::Legacy, Some(key)) => Domain::Keyed(key.0),
+            (Version::V1, None) => Domain::Context(match role {
+                Role::Head => "imprint v1 head sample",
+                Role::Tail => "imprint v1 tail sample",
+                Role::Interior => "imprint v1 interior sample",
+            }),
+            (Version::V1, Some(key)) => {
+                let context = match role {
+                    Role::Head => "imprint v1 keyed head sample",
+                    Role::Tail => "imprint v1 keyed tail sample",
+                    Role::Interior => "imprint v1 keyed interior sample",
+                };
+                Domain::Keyed(blake3::derive_key(context, &key.0))
+            }
+        };
+        Sampler::new(self.algorithm, domain)
+    }
+
+    /// Returns a hasher whose output determines derived interior sample positions.
+    ///
+    /// Keyed schemes derive positions from the key, so they cannot be predicted without it.
+    pub(crate) fn position_hasher(&self) -> Hasher {
+        match &self.key {
+            None => Hasher::new_derive_key("imprint interior sample positions"),
+            Some(key) => Hasher::new_keyed(&blake3::derive_key(
+                "imprint keyed interior sample positions",
+                &key.0,
+            )),
+        }
+    }
+
+    pub(crate) fn key_id(&self) -> Option<KeyId> {
+        self.key.map(|key| {
+            let mut id = [0; 16];
+            let hash = blake3::derive_key("imprint key id", &key.0);
+            id.copy_from_slice(&hash[..16]);
+            KeyId(id)
+        })
+    }
+}
+
+impl KeyId {
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    /// Parses the hex form written by `Display`.
+    pub(crate) fn from_hex(hex: &str) -> Option<Self> {
+        let mut id = [0; 16];
+        decode_hex(hex, &mut id)?;
+        Some(KeyId(id))
+    }
+}
+
+impl Display for KeyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Imprinter, SAMPLE_SIZE};
+
+    use super::*;
+
+    #[test]
+    fn legacy_samples_are_plain_blake3() {
+        let content: Vec<u8> = (0..0x50000u32).map(|i| (i * 13 + i / 257) as u8).collect();
+        let tail = &content[content.len() - SAMPLE_SIZE as usize..];
+        let mut imprinter = Imprinter::new().version(Version::Legacy);
+
+        let imprint = imprinter.imprint_memory(&content).unwrap();
+        let head = blake3::hash(&content[..SAMPLE_SIZE as usize]);
+        assert_eq!(imprint.head().as_bytes(), head.as_bytes());
+        assert_eq!(
+            imprint.tail().unwrap().as_bytes(),
+            blake3::hash(tail).as_bytes()
+        );
+
+        let short = &content[..1000];
+        let imprint = imprinter.imprint_memory(short).unwrap();
+        assert_eq!(imprint.head().as_bytes(), blake3::hash(short).as_bytes());
+        assert_eq!(imprint.tail(), None);
+
+        let imprint = Imprinter::new().imprint_memory(short).unwrap();
+        assert_ne!(imprint.head().as_bytes(), blake3::hash(short).as_bytes());
+    }
+}
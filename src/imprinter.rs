@@ -0,0 +1,366 @@
+use std::{
+    fs::{self, File},
+    io::{Cursor, ErrorKind, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use blake3::KEY_LEN;
+
+use crate::{
+    hashing::{Key, Scheme},
+    imprint_sampled, sample_buffer,
+    stamp::Stamp,
+    stream::Stream,
+    Algorithm, Imprint, ImprintError, Interior, ProgressiveImprint, Sampling, Version, SAMPLE_SIZE,
+};
+
+/// The interior sampling used by keyed imprinters unless a derived sampling is configured.
+const KEYED_INTERIOR: Interior = Interior::Derived {
+    count: 16,
+    size: 4096,
+};
+
+/// Builds imprints using configurable sampling.
+///
+/// An imprinter owns its sample buffer, so reusing one imprinter for many files avoids an
+/// allocation per file.
+#[derive(Clone, Debug, Default)]
+pub struct Imprinter {
+    sampling: Sampling,
+    scheme: Scheme,
+    pub(crate) retries: u32,
+    buffer: Box<[u8]>,
+}
+
+impl Imprinter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of bytes sampled from the start of the content.
+    pub fn head_size(mut self, size: u64) -> Self {
+        self.sampling.head = size;
+        self
+    }
+
+    /// Sets the number of bytes sampled from the end of the content.
+    pub fn tail_size(mut self, size: u64) -> Self {
+        self.sampling.tail = size;
+        self
+    }
+
+    /// Sets the strategy used to sample the content between the head and tail.
+    ///
+    /// A keyed imprinter always samples derived positions; see `Imprinter::key`.
+    pub fn interior(mut self, interior: Interior) -> Self {
+        self.sampling.interior = interior;
+        self.derive_keyed_interior();
+        self
+    }
+
+    /// Sets the version of the scheme used to hash samples.
+    ///
+    /// New imprints use `Version::V1` by default. Use `Version::Legacy` to produce imprints
+    /// comparable with those made by earlier releases.
+    pub fn version(mut self, version: Version) -> Self {
+        self.scheme.version = version;
+        self
+    }
+
+    /// Sets the algorithm used to hash samples. The default is BLAKE3.
+    ///
+    /// Only imprints made with the same algorithm are comparable.
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.scheme.algorithm = algorithm;
+        self
+    }
+
+    /// Sets a secret key for keyed imprinting.
+    ///
+    /// Keyed imprints hash every sample with the key and derive interior sample positions from
+    /// it. Since the head and tail are sampled at known positions, keyed imprinting always
+    /// samples the interior too: `Interior::None` is replaced by 16 derived windows of 4 KiB,
+    /// and `Interior::Even` by derived windows of the same count and size.
+    ///
+    /// Without the key, an attacker cannot tell which interior bytes are sampled, so content
+    /// edited between the head and tail is detected unless every edit happens to avoid every
+    /// window. Sampling still leaves most bytes unread: a small edit escapes detection with a
+    /// probability close to the fraction of the interior left unsampled, so use full hashing
+    /// where every byte matters.
+    ///
+    /// Only imprints made with the same key are comparable; each imprint records an identifier
+    /// of its key, not the key itself.
+    pub fn key(mut self, key: [u8; KEY_LEN]) -> Self {
+        self.scheme.key = Some(Key(key));
+        self.derive_keyed_interior();
+        self
+    }
+
+    /// Ensures a keyed imprinter samples the interior at positions derived from its key.
+    fn derive_keyed_interior(&mut self) {
+        if self.scheme.key.is_none() {
+            return;
+        }
+
+        self.sampling.interior = match self.sampling.interior {
+            Interior::Derived { count, size } | Interior::Even { count, size }
+                if count > 0 && size > 0 =>
+            {
+                Interior::Derived { count, size }
+            }
+            _ => KEYED_INTERIOR,
+        };
+    }
+
+    /// Sets how many times to retry imprinting a file that is modified while it is read.
+    ///
+    /// Once retries are exhausted, imprinting fails with `ImprintError::Unstable` or
+    /// `ImprintError::SizeChanged`. By default, no retries are made.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn sampling(&self) -> Sampling {
+        self.sampling
+    }
+
+    pub(crate) fn scheme(&self) -> Scheme {
+        self.scheme
+    }
+
+    /// True if the imprint was made with this imprinter's sampling and hashing scheme, so that
+    /// it is the imprint this imprinter would make of the same content.
+    pub(crate) fn would_make(&self, imprint: &Imprint) -> bool {
+        imprint.sampling == self.sampling
+            && imprint.version == self.scheme.version
+            && imprint.algorithm == self.scheme.algorithm
+            && imprint.key_id == self.scheme.key_id()
+    }
+
+    pub fn imprint(&mut self, path: impl AsRef<Path>) -> Result<Imprint, ImprintError> {
+        let path = path.as_ref();
+        self.imprint_file(path).map_err(|e| e.with_path(path))
+    }
+
+    fn imprint_file(&mut self, path: &Path) -> Result<Imprint, ImprintError> {
+        let retries = self.retries;
+        retry(retries, || self.imprint_file_once(path))
+    }
+
+    /// Imprints a file, failing if it changes between the start and end of sampling.
+    fn imprint_file_once(&mut self, path: &Path) -> Result<Imprint, ImprintError> {
+        ImprintError::check_file(path, &fs::metadata(path)?)?;
+
+        let mut file = File::open(path)?;
+        let before = Stamp::new(&file.metadata()?);
+        let imprint = self.imprint_reader(&mut file, Some(before.len))?;
+        before.check(&Stamp::new(&file.metadata()?))?;
+
+        Ok(imprint)
+    }
+
+    pub fn imprint_memory(&mut self, buf: &[u8]) -> Result<Imprint, ImprintError> {
+        self.imprint_reader(Cursor::new(buf), Some(buf.len() as u64))
+    }
+
+    /// Imprints content from a reader that cannot seek, such as a pipe or network stream.
+    ///
+    /// The result is identical to imprinting the same bytes from a file. Interior sampling
+    /// requires `len`; if given, the stream must end at exactly that length.
+    pub fn imprint_stream(
+        &mut self,
+        mut reader: impl Read,
+        len: Option<u64>,
+    ) -> Result<Imprint, ImprintError> {
+        let mut stream = Stream::new(self.sampling, self.scheme, len)?;
+        let buffer = sample_buffer(&mut self.buffer, SAMPLE_SIZE);
+        loop {
+            match reader.read(buffer) {
+                Ok(0) => break,
+                Ok(n) => stream.update(&buffer[..n]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        stream.finish()
+    }
+
+    /// Opens a file for progressive imprinting using this imprinter's sampling.
+    pub fn progressive(&self, path: impl AsRef<Path>) -> Result<ProgressiveImprint, ImprintError> {
+        ProgressiveImprint::open_with(path, self.sampling, self.scheme)
+    }
+
+    /// Imprints the content of any seekable reader, from its start.
+    ///
+    /// If the length of the content is not known, it is found by seeking to the end.
+    pub fn imprint_reader(
+        &mut self,
+        mut reader: impl Read + Seek,
+        len: Option<u64>,
+    ) -> Result<Imprint, ImprintError> {
+        let len = match len {
+            Some(len) => len,
+            None => reader.seek(SeekFrom::End(0))?,
+        };
+
+        let (sampling, scheme, buffer) = self.parts();
+        imprint_sampled(&mut reader, buffer, len, sampling, scheme)
+            .map_err(|e| ImprintError::reading(e, len))
+    }
+
+    /// The sampling and hashing scheme, along with a scratch buffer sized for the sampling.
+    pub(crate) fn parts(&mut self) -> (Sampling, Scheme, &mut [u8]) {
+        (
+            self.sampling,
+            self.scheme,
+            sample_buffer(&mut self.buffer, self.sampling.max_window()),
+        )
+    }
+}
+
+/// Makes an attempt, repeating it up to `retries` more times while it fails because the file was
+/// changing.
+fn retry<T>(
+    retries: u32,
+    mut attempt: impl FnMut() -> Result<T, ImprintError>,
+) -> Result<T, ImprintError> {
+    let mut attempts = 0;
+    loop {
+        match attempt() {
+            Err(e) if e.is_unstable() && attempts < retries => attempts += 1,
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; KEY_LEN] = [7; KEY_LEN];
+
+    /// A mebibyte of content whose head and tail samples are unchanged but whose interior is
+    /// entirely rewritten.
+    fn edited_interior() -> (Vec<u8>, Vec<u8>) {
+        let original: Vec<u8> = (0..0x100000u32).map(|i| (i % 251) as u8).collect();
+        let mut edited = original.clone();
+        for byte in &mut edited[SAMPLE_SIZE as usize..0x100000 - SAMPLE_SIZE as usize] {
+            *byte ^= 0xff;
+        }
+        (original, edited)
+    }
+
+    #[test]
+    fn keyed_imprint_detects_edited_interior() {
+        let (original, edited) = edited_interior();
+        let mut imprinter = Imprinter::new().key(KEY);
+        assert_ne!(
+            imprinter.imprint_memory(&original).unwrap(),
+            imprinter.imprint_memory(&edited).unwrap()
+        );
+    }
+
+    #[test]
+    fn unkeyed_imprint_ignores_interior_by_default() {
+        let (original, edited) = edited_interior();
+        let mut imprinter = Imprinter::new();
+        assert_eq!(
+            imprinter.imprint_memory(&original).unwrap(),
+            imprinter.imprint_memory(&edited).unwrap()
+        );
+    }
+
+    #[test]
+    fn key_derives_interior_positions() {
+        let derived = Interior::Derived {
+            count: 3,
+            size: 512,
+        };
+        let even = Interior::Even {
+            count: 3,
+            size: 512,
+        };
+
+        assert_eq!(
+            Imprinter::new().key(KEY).sampling().interior(),
+            KEYED_INTERIOR
+        );
+        assert_eq!(
+            Imprinter::new()
+                .interior(even)
+                .key(KEY)
+                .sampling()
+                .interior(),
+            derived
+        );
+        assert_eq!(
+            Imprinter::new()
+                .key(KEY)
+                .interior(even)
+                .sampling()
+                .interior(),
+            derived
+        );
+        assert_eq!(
+            Imprinter::new()
+                .key(KEY)
+                .interior(Interior::None)
+                .sampling()
+                .interior(),
+            KEYED_INTERIOR
+        );
+        assert_eq!(Imprinter::new().interior(even).sampling().interior(), even);
+    }
+
+    #[test]
+    fn unstable_attempts_are_retried_up_to_the_limit() {
+        for retries in 0..3 {
+            let mut attempts = 0;
+            let result: Result<(), _> = retry(retries, || {
+                attempts += 1;
+                Err(ImprintError::Unstable { path: None })
+            });
+            assert!(matches!(result, Err(ImprintError::Unstable { .. })));
+            assert_eq!(attempts, retries + 1);
+        }
+
+        let mut attempts = 0;
+        let result = retry(2, || {
+            attempts += 1;
+            match attempts {
+                1 => Err(ImprintError::SizeChanged {
+                    path: None,
+                    expected: 1,
+                    actual: 2,
+                }),
+                2 => Err(ImprintError::Truncated {
+                    path: None,
+                    expected: 2,
+                }),
+                _ => Ok(attempts),
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        let mut attempts = 0;
+        let result: Result<(), _> = retry(2, || {
+            attempts += 1;
+            Err(ImprintError::KeyRequired)
+        });
+        assert!(matches!(result, Err(ImprintError::KeyRequired)));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn content_shorter_than_its_length_is_unstable() {
+        let error = Imprinter::new()
+            .imprint_reader(Cursor::new(vec![0; 100]), Some(200))
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ImprintError::Truncated { expected: 200, .. }
+        ));
+        assert!(error.is_unstable());
+    }
+}
@@ -1,84 +1,325 @@
+#[cfg(feature = "futures-io")]
+mod async_futures;
+#[cfg(feature = "tokio")]
+mod async_tokio;
+mod batch;
+mod cache;
+mod diff;
+mod digest;
+mod dupes;
+mod encoding;
+mod error;
+mod full;
+mod hashing;
+mod imprinter;
+mod manifest;
+mod platform;
+mod prefix;
+mod progressive;
+mod sampling;
+#[cfg(feature = "serde")]
+mod serialize;
+mod stamp;
+mod stream;
+mod tee;
+mod text;
+#[cfg(feature = "walk")]
+mod walk;
+#[cfg(all(unix, feature = "xattr"))]
+mod xattr_store;
+
 use std::{
     fmt::Display,
-    fs,
-    io::{self, Cursor, Read, Seek, SeekFrom},
+    io::{self, Read, Seek, SeekFrom},
+    ops::Range,
     path::Path,
 };
 
 use blake3::{Hash, Hasher};
+use digest::Digest;
+use hashing::{Role, Scheme};
+
+pub use batch::{Batch, ImprintMany, Order};
+pub use cache::Cache;
+pub use diff::{Change, Diff};
+pub use digest::{Algorithm, SampleHash};
+pub use dupes::{DuplicateFinder, DuplicateGroup, Duplicates};
+pub use error::{DecodeError, FileKind, ImprintError};
+pub use full::{verify_same_content, FullImprint};
+pub use hashing::{KeyId, Version};
+pub use imprinter::Imprinter;
+pub use manifest::{Manifest, Verification};
+pub use prefix::{Prefixes, Resolution};
+pub use progressive::{Comparison, Level, ProgressiveImprint};
+pub use sampling::{Interior, Sampling};
+pub use tee::{ImprintReader, ImprintWriter};
+#[cfg(feature = "walk")]
+pub use walk::{Files, Walk, WalkEntry, Walker};
+#[cfg(all(unix, feature = "xattr"))]
+pub use xattr_store::{XattrStore, XATTR_NAME};
+
+#[cfg(feature = "walk")]
+pub use globset::Glob;
 
-/// Sample size for head and tail segments.
+/// Default sample size for head and tail segments.
 ///
 /// This sample is 128kb in length, which should be more than sufficient.
 const SAMPLE_SIZE: u64 = 0x20000;
 
+/// Chunk size for multithreaded full hashing.
+#[cfg(feature = "rayon")]
+const PARALLEL_CHUNK: u64 = 0x400000;
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct Imprint {
-    head: Hash,
-    tail: Option<Hash>,
+    len: u64,
+    sampling: Sampling,
+    version: Version,
+    algorithm: Algorithm,
+    key_id: Option<KeyId>,
+    head: SampleHash,
+    tail: Option<SampleHash>,
+    interior: Box<[SampleHash]>,
 }
 
 impl Imprint {
-    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
-        use std::fs::File;
-
-        let path = path.as_ref();
-        let meta = fs::metadata(path)?;
-        if !meta.is_file() {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "received a directory when expecting a file",
-            ));
-        }
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, ImprintError> {
+        Imprinter::new().imprint(path)
+    }
+
+    pub fn from_memory(buf: &[u8]) -> Result<Self, ImprintError> {
+        Imprinter::new().imprint_memory(buf)
+    }
+
+    /// Imprints the content of any seekable reader, such as an already-open file.
+    ///
+    /// If `len` is `None`, the length of the content is found by seeking to the end.
+    pub fn from_reader(reader: impl Read + Seek, len: Option<u64>) -> Result<Self, ImprintError> {
+        Imprinter::new().imprint_reader(reader, len)
+    }
+
+    /// Imprints content from a reader that cannot seek, such as a pipe or standard input.
+    pub fn from_stream(reader: impl Read) -> Result<Self, ImprintError> {
+        Imprinter::new().imprint_stream(reader, None)
+    }
+
+    /// The length, in bytes, of the imprinted content.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// True if the imprinted content was empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
 
-        let len = meta.len();
-        let mut reader = File::open(path)?;
-        let mut buffer = vec![0; SAMPLE_SIZE as usize].into_boxed_slice();
+    /// The sampling parameters this imprint was made with.
+    pub fn sampling(&self) -> Sampling {
+        self.sampling
+    }
 
-        Ok(Imprint {
-            head: hash_head(&mut reader, &mut buffer, len)?,
-            tail: hash_tail(&mut reader, &mut buffer, len)?,
-        })
+    /// The version of the scheme used to hash the samples.
+    pub fn version(&self) -> Version {
+        self.version
     }
 
-    pub fn from_memory(buf: &[u8]) -> io::Result<Self> {
-        let len = buf.len() as u64;
-        let mut reader = Cursor::new(buf);
-        let mut buffer = vec![0; SAMPLE_SIZE as usize].into_boxed_slice();
+    /// The algorithm used to hash the samples.
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
 
-        Ok(Imprint {
-            head: hash_head(&mut reader, &mut buffer, len)?,
-            tail: hash_tail(&mut reader, &mut buffer, len)?,
-        })
+    /// The hash of the head sample.
+    pub fn head(&self) -> SampleHash {
+        self.head
+    }
+
+    /// The hash of the tail sample, if the content extends past the head sample.
+    pub fn tail(&self) -> Option<SampleHash> {
+        self.tail
+    }
+
+    /// The hashes of the interior samples, in order of position.
+    pub fn interior(&self) -> &[SampleHash] {
+        &self.interior
+    }
+
+    /// The identity of the key used to make a keyed imprint.
+    ///
+    /// Keyed and unkeyed imprints, or imprints made with different keys, never compare equal.
+    pub fn key_id(&self) -> Option<KeyId> {
+        self.key_id
+    }
+
+    /// True if the two imprints were made the same way, and so are equal for identical content.
+    ///
+    /// Imprints made with different sampling, hashing versions, algorithms or keys never compare
+    /// equal, whatever the content.
+    pub fn is_comparable(&self, other: &Imprint) -> bool {
+        self.sampling == other.sampling
+            && self.version == other.version
+            && self.algorithm == other.algorithm
+            && self.key_id == other.key_id
     }
 }
 
+/// Displays the head hash in hex, or with `{:#}`, the canonical text form of the whole imprint.
+///
+/// The canonical form can be parsed back with `str::parse`.
 impl Display for Imprint {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.head.fmt(f)
+        if f.alternate() {
+            self.write_canonical(f)
+        } else {
+            self.head.fmt(f)
+        }
     }
 }
 
-fn hash_head(reader: &mut impl Read, buf: &mut [u8], len: u64) -> io::Result<Hash> {
-    let len = len.min(SAMPLE_SIZE) as usize;
-    let buf = &mut buf[..len];
-    reader.read_exact(buf)?;
-    Ok(Hasher::new().update(buf).finalize())
+/// Imprints `len` bytes of content from the start of the reader.
+fn imprint_sampled(
+    reader: &mut (impl Read + Seek),
+    buf: &mut [u8],
+    len: u64,
+    sampling: Sampling,
+    scheme: Scheme,
+) -> io::Result<Imprint> {
+    let hashes = sampling
+        .windows(len, &scheme)
+        .into_iter()
+        .map(|(role, window)| hash_window(reader, buf, window, scheme.hasher(role)))
+        .collect::<io::Result<_>>()?;
+    Ok(assemble(len, sampling, &scheme, hashes))
 }
 
-fn hash_tail(
+/// Makes an imprint from the hashes of the windows given by `Sampling::windows`, in order.
+fn assemble(len: u64, sampling: Sampling, scheme: &Scheme, hashes: Vec<SampleHash>) -> Imprint {
+    let mut hashes = hashes.into_iter();
+    let head = hashes.next().expect("the head is always sampled");
+    let tail = if sampling.tail_len(len) > 0 {
+        hashes.next()
+    } else {
+        None
+    };
+
+    Imprint {
+        len,
+        sampling,
+        version: scheme.version,
+        algorithm: scheme.algorithm,
+        key_id: scheme.key_id(),
+        head,
+        tail,
+        interior: hashes.collect(),
+    }
+}
+
+fn hash_head<D: Digest>(
+    reader: &mut impl Read,
+    buf: &mut [u8],
+    len: u64,
+    sampling: Sampling,
+    hasher: D,
+) -> io::Result<D::Output> {
+    hash_exact(reader, buf, sampling.head_len(len), hasher)
+}
+
+fn hash_tail<D: Digest>(
     reader: &mut (impl Read + Seek),
     buf: &mut [u8],
     len: u64,
-) -> io::Result<Option<Hash>> {
-    let tail_len = len.saturating_sub(SAMPLE_SIZE);
+    sampling: Sampling,
+    hasher: D,
+) -> io::Result<Option<D::Output>> {
+    let tail_len = sampling.tail_len(len);
     if tail_len == 0 {
         return Ok(None);
     }
 
-    let len = tail_len.min(SAMPLE_SIZE) as usize;
-    let buf = &mut buf[..len];
-    reader.seek(SeekFrom::End(-(len as i64)))?;
-    reader.read_exact(buf)?;
-    Ok(Some(Hasher::new().update(buf).finalize()))
+    hash_window(reader, buf, len - tail_len..len, hasher).map(Some)
+}
+
+fn hash_interior(
+    reader: &mut (impl Read + Seek),
+    buf: &mut [u8],
+    len: u64,
+    sampling: Sampling,
+    scheme: &Scheme,
+) -> io::Result<Box<[SampleHash]>> {
+    sampling
+        .interior_windows(len, scheme)
+        .into_iter()
+        .map(|window| hash_window(reader, buf, window, scheme.hasher(Role::Interior)))
+        .collect()
+}
+
+/// Hashes the entire content, from the start of the reader.
+///
+/// With the `rayon` feature, content larger than a single chunk is hashed using blake3's
+/// multithreaded update.
+fn hash_full(reader: &mut (impl Read + Seek), buf: &mut Box<[u8]>, len: u64) -> io::Result<Hash> {
+    reader.seek(SeekFrom::Start(0))?;
+
+    #[cfg(feature = "rayon")]
+    if len > PARALLEL_CHUNK {
+        let mut buf = vec![0; PARALLEL_CHUNK as usize];
+        let mut hasher = Hasher::new();
+        read_chunks(reader, &mut buf, len, |chunk| {
+            hasher.update_rayon(chunk);
+        })?;
+        return Ok(hasher.finalize());
+    }
+
+    hash_exact(reader, sample_buffer(buf, SAMPLE_SIZE), len, Hasher::new())
+}
+
+/// Hashes the bytes of the window, using `buf` as scratch space.
+fn hash_window<D: Digest>(
+    reader: &mut (impl Read + Seek),
+    buf: &mut [u8],
+    window: Range<u64>,
+    hasher: D,
+) -> io::Result<D::Output> {
+    reader.seek(SeekFrom::Start(window.start))?;
+    hash_exact(reader, buf, window.end - window.start, hasher)
+}
+
+/// Hashes exactly `len` bytes from the reader, using `buf` as scratch space.
+fn hash_exact<D: Digest>(
+    reader: &mut impl Read,
+    buf: &mut [u8],
+    len: u64,
+    mut hasher: D,
+) -> io::Result<D::Output> {
+    read_chunks(reader, buf, len, |chunk| {
+        hasher.update(chunk);
+    })?;
+    Ok(hasher.finalize())
+}
+
+/// Reads exactly `len` bytes from the reader in buffer-sized chunks.
+fn read_chunks(
+    reader: &mut impl Read,
+    buf: &mut [u8],
+    mut len: u64,
+    mut f: impl FnMut(&[u8]),
+) -> io::Result<()> {
+    while len > 0 {
+        let chunk = len.min(buf.len() as u64);
+        let buf = &mut buf[..chunk as usize];
+        reader.read_exact(buf)?;
+        f(buf);
+        len -= chunk;
+    }
+    Ok(())
+}
+
+/// Returns a scratch buffer for hashing regions of up to `size` bytes, allocating it on first use.
+///
+/// Regions are hashed in chunks, so the buffer never grows past the default sample size.
+fn sample_buffer(buffer: &mut Box<[u8]>, size: u64) -> &mut [u8] {
+    let size = size.min(SAMPLE_SIZE) as usize;
+    if buffer.len() < size {
+        *buffer = vec![0; size].into_boxed_slice();
+    }
+    buffer
 }
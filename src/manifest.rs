@@ -0,0 +1,256 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, Write},
+    iter::FromIterator,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    platform::{bytes_path, path_bytes},
+    Imprint, ImprintError,
+};
+
+/// The comment that begins a manifest written by this crate.
+const HEADER: &str = "# imprint manifest v1";
+
+/// A record of the files in a tree, keyed by their paths relative to its root.
+///
+/// The text form has one line per file, sorted by path, so that manifests of the same tree
+/// diff cleanly. Each line holds the path, the length and the canonical imprint, separated by
+/// tabs:
+///
+/// ```text
+/// src/lib.rs<TAB>18324<TAB>imp2.v1.18324.131072-131072.<head>
+/// ```
+///
+/// Backslashes, control characters and bytes that are not valid UTF-8 are escaped in paths as
+/// `\\`, `\t`, `\n`, `\r` or `\xHH`, as is a `#` that begins a path, so any path is written
+/// losslessly on one line. Blank lines and lines beginning with `#` are ignored.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Manifest {
+    entries: BTreeMap<PathBuf, Imprint>,
+}
+
+/// The result of verifying a tree against a manifest.
+///
+/// Paths are relative to the root of the tree, and each list is sorted.
+#[derive(Debug, Default)]
+pub struct Verification {
+    pub(crate) unchanged: Vec<PathBuf>,
+    pub(crate) modified: Vec<PathBuf>,
+    pub(crate) added: Vec<PathBuf>,
+    pub(crate) missing: Vec<PathBuf>,
+    pub(crate) errors: Vec<ImprintError>,
+}
+
+impl Manifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the imprint of the file at `path`, returning the imprint it replaces.
+    pub fn insert(&mut self, path: impl Into<PathBuf>, imprint: Imprint) -> Option<Imprint> {
+        self.entries.insert(path.into(), imprint)
+    }
+
+    pub fn remove(&mut self, path: impl AsRef<Path>) -> Option<Imprint> {
+        self.entries.remove(path.as_ref())
+    }
+
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&Imprint> {
+        self.entries.get(path.as_ref())
+    }
+
+    /// The number of files in the manifest.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The paths and imprints of the files in the manifest, in sorted order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &Imprint)> + '_ {
+        self.entries
+            .iter()
+            .map(|(path, imprint)| (path.as_path(), imprint))
+    }
+
+    /// Reads a manifest from its text form.
+    ///
+    /// A line that cannot be parsed is reported as an `InvalidData` error naming its line
+    /// number.
+    pub fn read(reader: impl BufRead) -> io::Result<Self> {
+        let mut manifest = Manifest::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (path, imprint) = parse_line(&line).map_err(|reason| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("manifest line {}: {}", index + 1, reason),
+                )
+            })?;
+            manifest.insert(path, imprint);
+        }
+        Ok(manifest)
+    }
+
+    /// Writes the text form of the manifest.
+    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "{}", HEADER)?;
+        for (path, imprint) in &self.entries {
+            writeln!(
+                writer,
+                "{}\t{}\t{:#}",
+                escape(path)?,
+                imprint.len(),
+                imprint
+            )?;
+        }
+        writer.flush()
+    }
+}
+
+impl<P: Into<PathBuf>> FromIterator<(P, Imprint)> for Manifest {
+    fn from_iter<I: IntoIterator<Item = (P, Imprint)>>(iter: I) -> Self {
+        Manifest {
+            entries: iter
+                .into_iter()
+                .map(|(path, imprint)| (path.into(), imprint))
+                .collect(),
+        }
+    }
+}
+
+impl Verification {
+    /// Files whose content matches the manifest.
+    pub fn unchanged(&self) -> &[PathBuf] {
+        &self.unchanged
+    }
+
+    /// Files whose content no longer matches the manifest.
+    pub fn modified(&self) -> &[PathBuf] {
+        &self.modified
+    }
+
+    /// Files found in the tree but not in the manifest.
+    pub fn added(&self) -> &[PathBuf] {
+        &self.added
+    }
+
+    /// Files in the manifest but not found in the tree.
+    pub fn missing(&self) -> &[PathBuf] {
+        &self.missing
+    }
+
+    /// The errors encountered for files that could not be examined.
+    pub fn errors(&self) -> &[ImprintError] {
+        &self.errors
+    }
+
+    pub fn into_errors(self) -> Vec<ImprintError> {
+        self.errors
+    }
+
+    /// True if every file matches the manifest and none were added or could not be examined.
+    pub fn is_clean(&self) -> bool {
+        self.modified.is_empty()
+            && self.added.is_empty()
+            && self.missing.is_empty()
+            && self.errors.is_empty()
+    }
+}
+
+fn parse_line(line: &str) -> Result<(PathBuf, Imprint), String> {
+    let mut fields = line.split('\t');
+    let (path, len, imprint) = match (fields.next(), fields.next(), fields.next(), fields.next()) {
+        (Some(path), Some(len), Some(imprint), None) => (path, len, imprint),
+        _ => return Err("expected a path, a length and an imprint".into()),
+    };
+
+    let path = unescape(path)?;
+    let len: u64 = len.parse().map_err(|_| "invalid length".to_string())?;
+    let imprint: Imprint = imprint.parse().map_err(|e| format!("{}", e))?;
+    if imprint.len() != len {
+        return Err("length does not match the imprint".into());
+    }
+    Ok((path, imprint))
+}
+
+fn escape(path: &Path) -> io::Result<String> {
+    let mut bytes = path_bytes(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: path is not valid Unicode", path.display()),
+        )
+    })?;
+    let mut escaped = String::with_capacity(bytes.len());
+    if bytes.first() == Some(&b'#') {
+        escaped.push_str("\\x23");
+        bytes = &bytes[1..];
+    }
+
+    while !bytes.is_empty() {
+        let (valid, invalid) = match std::str::from_utf8(bytes) {
+            Ok(valid) => (valid, &[][..]),
+            Err(e) => {
+                let (valid, rest) = bytes.split_at(e.valid_up_to());
+                let invalid = e.error_len().unwrap_or(rest.len());
+                (std::str::from_utf8(valid).unwrap(), &rest[..invalid])
+            }
+        };
+
+        for c in valid.chars() {
+            match c {
+                '\\' => escaped.push_str("\\\\"),
+                '\t' => escaped.push_str("\\t"),
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                c if c.is_ascii_control() => escaped.push_str(&format!("\\x{:02x}", c as u8)),
+                c => escaped.push(c),
+            }
+        }
+        for byte in invalid {
+            escaped.push_str(&format!("\\x{:02x}", byte));
+        }
+        bytes = &bytes[valid.len() + invalid.len()..];
+    }
+    Ok(escaped)
+}
+
+fn unescape(escaped: &str) -> Result<PathBuf, String> {
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut rest = escaped.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+
+        let (&kind, tail) = rest.split_first().ok_or("incomplete escape in path")?;
+        rest = tail;
+        bytes.push(match kind {
+            b'\\' => b'\\',
+            b't' => b'\t',
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b'x' if rest.len() >= 2 => {
+                let hex = std::str::from_utf8(&rest[..2]).map_err(|_| "invalid escape in path")?;
+                rest = &rest[2..];
+                u8::from_str_radix(hex, 16).map_err(|_| "invalid escape in path")?
+            }
+            _ => return Err("invalid escape in path".into()),
+        });
+    }
+
+    if bytes.is_empty() {
+        return Err("empty path".into());
+    }
+    bytes_path(bytes).ok_or_else(|| "path is not valid Unicode".to_string())
+}
@@ -0,0 +1,35 @@
+//! Platform-specific identification of files and their paths.
+
+use std::path::{Path, PathBuf};
+
+/// Identifies a file independently of its content and of the paths that link to it.
+#[cfg(unix)]
+pub(crate) type FileId = (u64, u64);
+
+/// Identifies a file independently of its content. Without inodes, a path is used.
+#[cfg(not(unix))]
+pub(crate) type FileId = PathBuf;
+
+/// The bytes of a path, as stored in manifests and caches.
+#[cfg(unix)]
+pub(crate) fn path_bytes(path: &Path) -> Option<&[u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Some(path.as_os_str().as_bytes())
+}
+
+/// Paths on other platforms are stored only if they are valid Unicode.
+#[cfg(not(unix))]
+pub(crate) fn path_bytes(path: &Path) -> Option<&[u8]> {
+    path.to_str().map(str::as_bytes)
+}
+
+#[cfg(unix)]
+pub(crate) fn bytes_path(bytes: Vec<u8>) -> Option<PathBuf> {
+    use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+    Some(OsString::from_vec(bytes).into())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn bytes_path(bytes: Vec<u8>) -> Option<PathBuf> {
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
@@ -0,0 +1,210 @@
+use std::collections::HashSet;
+
+use crate::Imprint;
+
+/// Short, git-style identifiers for a collection of imprints.
+///
+/// Identifiers are prefixes of the hex head hash printed by `Display`. Each prefix is the
+/// shortest that distinguishes its imprint from every other imprint in the collection, so
+/// prefixes remain unambiguous only with respect to that collection.
+#[derive(Clone, Debug)]
+pub struct Prefixes<'a> {
+    imprints: &'a [Imprint],
+    sorted: Vec<(String, usize)>,
+    min_len: usize,
+}
+
+/// The result of resolving a prefix against a collection of imprints.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Resolution<'a> {
+    /// No imprint matches the prefix.
+    NotFound,
+
+    /// Exactly one distinct imprint matches the prefix.
+    Unique(&'a Imprint),
+
+    /// Several distinct imprints match the prefix.
+    Ambiguous(Vec<&'a Imprint>),
+}
+
+impl<'a> Prefixes<'a> {
+    pub fn new(imprints: &'a [Imprint]) -> Self {
+        let mut sorted: Vec<_> = imprints
+            .iter()
+            .enumerate()
+            .map(|(idx, imprint)| (imprint.to_string(), idx))
+            .collect();
+        sorted.sort_unstable();
+
+        Prefixes {
+            imprints,
+            sorted,
+            min_len: 4,
+        }
+    }
+
+    /// Sets the minimum length of the prefixes returned. The default is 4.
+    pub fn min_len(mut self, min_len: usize) -> Self {
+        self.min_len = min_len;
+        self
+    }
+
+    /// The shortest unique prefix of each imprint, in the order of the collection.
+    ///
+    /// Equal imprints share a prefix. Distinct imprints with the same head hash cannot be told
+    /// apart by any prefix, and are given the full head hash.
+    pub fn shortest(&self) -> Vec<String> {
+        let mut prefixes = vec![String::new(); self.imprints.len()];
+
+        // Imprints sharing a head hash are given one prefix, computed once per run so that
+        // collections with many copies of the same file take linear time.
+        let runs: Vec<&[(String, usize)]> = self.sorted.chunk_by(|a, b| a.0 == b.0).collect();
+        for (pos, run) in runs.iter().enumerate() {
+            let (hex, first) = &run[0];
+            let shared = if run
+                .iter()
+                .any(|(_, idx)| self.imprints[*idx] != self.imprints[*first])
+            {
+                hex.len()
+            } else {
+                // In sorted order, the neighbouring runs share the longest common prefix with
+                // this one.
+                let before = pos.checked_sub(1).map(|pos| runs[pos]);
+                let after = runs.get(pos + 1).copied();
+                before
+                    .into_iter()
+                    .chain(after)
+                    .map(|other| common_prefix(hex, &other[0].0))
+                    .max()
+                    .unwrap_or(0)
+            };
+
+            let len = (shared + 1).max(self.min_len).min(hex.len());
+            for (_, idx) in run.iter() {
+                prefixes[*idx] = hex[..len].to_owned();
+            }
+        }
+        prefixes
+    }
+
+    /// Finds the imprints whose head hash begins with the given hex prefix.
+    pub fn resolve(&self, prefix: &str) -> Resolution<'a> {
+        let prefix = prefix.to_ascii_lowercase();
+        if prefix.is_empty() || !prefix.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Resolution::NotFound;
+        }
+
+        let start = self
+            .sorted
+            .partition_point(|(hex, _)| hex.as_str() < prefix.as_str());
+        let mut seen = HashSet::new();
+        let matches: Vec<&'a Imprint> = self.sorted[start..]
+            .iter()
+            .take_while(|(hex, _)| hex.starts_with(&prefix))
+            .map(|(_, idx)| &self.imprints[*idx])
+            .filter(|imprint| seen.insert(*imprint))
+            .collect();
+
+        match matches.len() {
+            0 => Resolution::NotFound,
+            1 => Resolution::Unique(matches[0]),
+            _ => Resolution::Ambiguous(matches),
+        }
+    }
+}
+
+fn common_prefix(a: &str, b: &str) -> usize {
+    a.bytes().zip(b.bytes()).take_while(|(a, b)| a == b).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Imprinter;
+
+    use super::*;
+
+    fn imprints(count: u32) -> Vec<Imprint> {
+        (0..count)
+            .map(|i| Imprint::from_memory(&i.to_le_bytes()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn shortest_prefixes_are_unique_and_minimal() {
+        let imprints = imprints(500);
+        let prefixes = Prefixes::new(&imprints).min_len(1);
+        for (prefix, imprint) in prefixes.shortest().iter().zip(&imprints) {
+            assert!(imprint.to_string().starts_with(prefix.as_str()));
+            assert_eq!(prefixes.resolve(prefix), Resolution::Unique(imprint));
+            if prefix.len() > 1 {
+                let shorter = &prefix[..prefix.len() - 1];
+                assert!(matches!(
+                    prefixes.resolve(shorter),
+                    Resolution::Ambiguous(_)
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn shortest_honors_min_len() {
+        let imprints = imprints(3);
+        let shortest = Prefixes::new(&imprints).shortest();
+        assert!(shortest.iter().all(|prefix| prefix.len() == 4));
+
+        let full = Prefixes::new(&imprints).min_len(1000).shortest();
+        for (prefix, imprint) in full.iter().zip(&imprints) {
+            assert_eq!(*prefix, imprint.to_string());
+        }
+    }
+
+    #[test]
+    fn equal_imprints_share_a_prefix() {
+        let mut imprints = imprints(2);
+        imprints.extend(vec![imprints[0].clone(); 10_000]);
+        let shortest = Prefixes::new(&imprints).min_len(1).shortest();
+        assert!(shortest[2..].iter().all(|prefix| *prefix == shortest[0]));
+        assert_ne!(shortest[0], shortest[1]);
+    }
+
+    #[test]
+    fn distinct_imprints_sharing_a_head_get_the_full_hash() {
+        let mut imprinter = Imprinter::new().head_size(4);
+        let mut imprints = imprints(2);
+        imprints.push(imprinter.imprint_memory(b"same head, one tail").unwrap());
+        imprints.push(
+            imprinter
+                .imprint_memory(b"same head, another tail")
+                .unwrap(),
+        );
+        assert_eq!(imprints[2].to_string(), imprints[3].to_string());
+
+        let prefixes = Prefixes::new(&imprints);
+        let shortest = prefixes.shortest();
+        assert_eq!(shortest[2], imprints[2].to_string());
+        assert_eq!(shortest[3], imprints[3].to_string());
+        assert_eq!(
+            prefixes.resolve(&shortest[2]),
+            Resolution::Ambiguous(vec![&imprints[2], &imprints[3]])
+        );
+    }
+
+    #[test]
+    fn resolve_matches_hex_prefixes() {
+        let imprints = imprints(2);
+        let prefixes = Prefixes::new(&imprints);
+        let hex = imprints[0].to_string();
+
+        assert_eq!(
+            prefixes.resolve(&hex[..8]),
+            Resolution::Unique(&imprints[0])
+        );
+        assert_eq!(
+            prefixes.resolve(&hex[..8].to_ascii_uppercase()),
+            Resolution::Unique(&imprints[0])
+        );
+        assert_eq!(prefixes.resolve(""), Resolution::NotFound);
+        assert_eq!(prefixes.resolve("xyz"), Resolution::NotFound);
+        assert_eq!(prefixes.resolve(&format!("{}0", hex)), Resolution::NotFound);
+    }
+}
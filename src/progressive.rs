@@ -0,0 +1,379 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use blake3::Hash;
+
+use crate::{
+    hash_full, hash_head, hash_interior, hash_tail,
+    hashing::{Role, Scheme},
+    sample_buffer,
+    stamp::Stamp,
+    Imprint, ImprintError, SampleHash, Sampling,
+};
+
+/// The stages through which a progressive comparison escalates, from cheapest to most expensive.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Level {
+    Length,
+    Head,
+    Tail,
+    Interior,
+    Full,
+}
+
+/// The result of a progressive comparison.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Comparison {
+    /// True if the content was found to be identical.
+    pub equal: bool,
+
+    /// The level that decided the comparison. Equal content is always decided at `Level::Full`.
+    pub level: Level,
+}
+
+/// An imprint that is computed only as far as a comparison requires.
+///
+/// Each level is cached once computed, so comparing one file against several others never reads
+/// the same region twice.
+#[derive(Debug)]
+pub struct ProgressiveImprint<R = File> {
+    reader: R,
+    path: Option<PathBuf>,
+    stamp: Option<Stamp>,
+    len: u64,
+    sampling: Sampling,
+    scheme: Scheme,
+    buffer: Box<[u8]>,
+    head: Option<SampleHash>,
+    tail: Option<Option<SampleHash>>,
+    interior: Option<Box<[SampleHash]>>,
+    full: Option<Hash>,
+}
+
+impl ProgressiveImprint {
+    /// Opens a file for progressive imprinting with default sampling.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ImprintError> {
+        Self::open_with(path, Sampling::default(), Scheme::default())
+    }
+
+    pub(crate) fn open_with(
+        path: impl AsRef<Path>,
+        sampling: Sampling,
+        scheme: Scheme,
+    ) -> Result<Self, ImprintError> {
+        let path = path.as_ref();
+        let open = || {
+            ImprintError::check_file(path, &fs::metadata(path)?)?;
+            let file = File::open(path)?;
+            let stamp = Stamp::new(&file.metadata()?);
+            Ok((file, stamp))
+        };
+
+        let (file, stamp) = open().map_err(|e: ImprintError| e.with_path(path))?;
+        let mut progressive = Self::with_params(file, stamp.len, sampling, scheme);
+        progressive.path = Some(path.into());
+        progressive.stamp = Some(stamp);
+        Ok(progressive)
+    }
+}
+
+impl<R: Read + Seek> ProgressiveImprint<R> {
+    /// Wraps a reader over `len` bytes of content, using default sampling.
+    pub fn new(reader: R, len: u64) -> Self {
+        Self::with_params(reader, len, Sampling::default(), Scheme::default())
+    }
+
+    pub(crate) fn with_params(reader: R, len: u64, sampling: Sampling, scheme: Scheme) -> Self {
+        ProgressiveImprint {
+            reader,
+            path: None,
+            stamp: None,
+            len,
+            sampling,
+            scheme,
+            buffer: Box::default(),
+            head: None,
+            tail: None,
+            interior: None,
+            full: None,
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn sampling(&self) -> Sampling {
+        self.sampling
+    }
+
+    /// The deepest level computed so far.
+    pub fn level(&self) -> Level {
+        if self.full.is_some() {
+            Level::Full
+        } else if self.interior.is_some() {
+            Level::Interior
+        } else if self.tail.is_some() {
+            Level::Tail
+        } else if self.head.is_some() {
+            Level::Head
+        } else {
+            Level::Length
+        }
+    }
+
+    pub fn head(&mut self) -> Result<SampleHash, ImprintError> {
+        if let Some(head) = self.head {
+            return Ok(head);
+        }
+
+        let (reader, len, sampling, scheme) =
+            (&mut self.reader, self.len, self.sampling, self.scheme);
+        let buffer = sample_buffer(&mut self.buffer, sampling.max_window());
+        let head = reader
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| hash_head(reader, buffer, len, sampling, scheme.hasher(Role::Head)))
+            .map_err(|e| error(e, len, &self.path))?;
+        Ok(*self.head.insert(head))
+    }
+
+    pub fn tail(&mut self) -> Result<Option<SampleHash>, ImprintError> {
+        if let Some(tail) = self.tail {
+            return Ok(tail);
+        }
+
+        let buffer = sample_buffer(&mut self.buffer, self.sampling.max_window());
+        let tail = hash_tail(
+            &mut self.reader,
+            buffer,
+            self.len,
+            self.sampling,
+            self.scheme.hasher(Role::Tail),
+        )
+        .map_err(|e| error(e, self.len, &self.path))?;
+        Ok(*self.tail.insert(tail))
+    }
+
+    pub fn interior(&mut self) -> Result<&[SampleHash], ImprintError> {
+        if self.interior.is_none() {
+            let buffer = sample_buffer(&mut self.buffer, self.sampling.max_window());
+            let interior = hash_interior(
+                &mut self.reader,
+                buffer,
+                self.len,
+                self.sampling,
+                &self.scheme,
+            )
+            .map_err(|e| error(e, self.len, &self.path))?;
+            self.interior = Some(interior);
+        }
+        Ok(self.interior.as_deref().unwrap_or_default())
+    }
+
+    /// The BLAKE3 hash of the entire content.
+    ///
+    /// For a file, fails if the file changed between being opened and being hashed, since the
+    /// hash would then describe content the file may no longer have.
+    pub fn full(&mut self) -> Result<Hash, ImprintError> {
+        if let Some(full) = self.full {
+            return Ok(full);
+        }
+
+        let full = hash_full(&mut self.reader, &mut self.buffer, self.len)
+            .map_err(|e| error(e, self.len, &self.path))?;
+        self.check_stable()?;
+        Ok(*self.full.insert(full))
+    }
+
+    /// Computes every sampled level and returns the equivalent imprint.
+    pub fn imprint(&mut self) -> Result<Imprint, ImprintError> {
+        Ok(Imprint {
+            len: self.len,
+            sampling: self.sampling,
+            version: self.scheme.version,
+            algorithm: self.scheme.algorithm,
+            key_id: self.scheme.key_id(),
+            head: self.head()?,
+            tail: self.tail()?,
+            interior: self.interior()?.into(),
+        })
+    }
+
+    /// Compares against another progressive imprint, stopping at the first level that differs.
+    ///
+    /// Sampled levels are skipped when the two imprints use different sampling or hashing, in
+    /// which case equal lengths escalate straight to a full hash.
+    pub fn compare<S: Read + Seek>(
+        &mut self,
+        other: &mut ProgressiveImprint<S>,
+    ) -> Result<Comparison, ImprintError> {
+        if self.len != other.len {
+            return Ok(Comparison::differ(Level::Length));
+        }
+
+        if self.sampling == other.sampling && self.scheme == other.scheme {
+            if self.head()? != other.head()? {
+                return Ok(Comparison::differ(Level::Head));
+            }
+            if self.tail()? != other.tail()? {
+                return Ok(Comparison::differ(Level::Tail));
+            }
+            if self.interior()? != other.interior()? {
+                return Ok(Comparison::differ(Level::Interior));
+            }
+        }
+
+        Ok(Comparison {
+            equal: self.full()? == other.full()?,
+            level: Level::Full,
+        })
+    }
+}
+
+impl<R> ProgressiveImprint<R> {
+    /// Fails if the file has changed since it was opened.
+    fn check_stable(&self) -> Result<(), ImprintError> {
+        if let (Some(before), Some(path)) = (&self.stamp, &self.path) {
+            let check = || before.check(&Stamp::new(&fs::metadata(path)?));
+            check().map_err(|e| e.with_path(path))?;
+        }
+        Ok(())
+    }
+}
+
+impl Comparison {
+    fn differ(level: Level) -> Self {
+        Comparison {
+            equal: false,
+            level,
+        }
+    }
+}
+
+/// Classifies an error encountered while reading `len` bytes of content from `path`.
+fn error(source: io::Error, len: u64, path: &Option<PathBuf>) -> ImprintError {
+    let error = ImprintError::reading(source, len);
+    match path {
+        Some(path) => error.with_path(path),
+        None => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use crate::{Interior, SAMPLE_SIZE};
+
+    use super::*;
+
+    const LEN: usize = 0x80000;
+
+    fn sampling() -> Sampling {
+        Sampling {
+            head: SAMPLE_SIZE,
+            tail: SAMPLE_SIZE,
+            interior: Interior::Even {
+                count: 4,
+                size: 4096,
+            },
+        }
+    }
+
+    fn progressive(content: &[u8], sampling: Sampling) -> ProgressiveImprint<Cursor<&[u8]>> {
+        let len = content.len() as u64;
+        ProgressiveImprint::with_params(Cursor::new(content), len, sampling, Scheme::default())
+    }
+
+    fn compare(a: &[u8], b: &[u8]) -> Comparison {
+        progressive(a, sampling())
+            .compare(&mut progressive(b, sampling()))
+            .unwrap()
+    }
+
+    fn edited(content: &[u8], pos: u64) -> Vec<u8> {
+        let mut edited = content.to_vec();
+        edited[pos as usize] ^= 0xff;
+        edited
+    }
+
+    #[test]
+    fn equal_content_is_decided_at_full() {
+        let content = vec![7; LEN];
+        let comparison = compare(&content, &content);
+        assert!(comparison.equal);
+        assert_eq!(comparison.level, Level::Full);
+    }
+
+    #[test]
+    fn differences_are_decided_at_their_level() {
+        let content: Vec<u8> = (0..LEN).map(|i| (i % 251) as u8).collect();
+        let window = sampling().interior_windows(LEN as u64, &Scheme::default())[1].clone();
+        let unsampled = window.end;
+
+        let cases = [
+            (content[1..].to_vec(), Level::Length),
+            (edited(&content, 10), Level::Head),
+            (edited(&content, LEN as u64 - 10), Level::Tail),
+            (edited(&content, window.start), Level::Interior),
+            (edited(&content, unsampled), Level::Full),
+        ];
+        for (other, level) in cases {
+            let comparison = compare(&content, &other);
+            assert!(!comparison.equal);
+            assert_eq!(comparison.level, level);
+        }
+    }
+
+    #[test]
+    fn mismatched_sampling_escalates_to_full() {
+        let content = vec![7; LEN];
+        let other = Sampling {
+            head: 1000,
+            ..sampling()
+        };
+
+        for (b, equal) in [(content.clone(), true), (edited(&content, 10), false)] {
+            let mut a = progressive(&content, sampling());
+            let mut b = progressive(&b, other);
+            let comparison = a.compare(&mut b).unwrap();
+            assert_eq!(comparison.equal, equal);
+            assert_eq!(comparison.level, Level::Full);
+            assert!(a.head.is_none() && b.head.is_none());
+        }
+    }
+
+    #[test]
+    fn files_changed_before_hashing_fail() {
+        let base = std::env::temp_dir().join(format!("imprint-changed-{}", std::process::id()));
+        fs::create_dir_all(&base).unwrap();
+        let (path, other) = (base.join("file"), base.join("other"));
+
+        fs::write(&path, b"content").unwrap();
+        let mut progressive = ProgressiveImprint::open(&path).unwrap();
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b" and more").unwrap();
+        assert!(matches!(
+            progressive.full(),
+            Err(ImprintError::SizeChanged { .. })
+        ));
+
+        fs::write(&path, b"content").unwrap();
+        let mut progressive = ProgressiveImprint::open(&path).unwrap();
+        fs::write(&other, b"replace").unwrap();
+        fs::rename(&other, &path).unwrap();
+        assert!(matches!(
+            progressive.full(),
+            Err(ImprintError::Unstable { .. })
+        ));
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+use std::ops::Range;
+
+use crate::{
+    hashing::{Role, Scheme},
+    SAMPLE_SIZE,
+};
+
+/// Sizes of the regions sampled from the start and end of the content, plus the strategy used to
+/// sample the interior between them.
+///
+/// The tail sample never overlaps the head sample; content no longer than the head sample has no
+/// tail at all. Interior windows fall strictly between the head and tail samples.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sampling {
+    pub(crate) head: u64,
+    pub(crate) tail: u64,
+    pub(crate) interior: Interior,
+}
+
+/// Strategy for sampling the region between the head and tail samples.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Interior {
+    /// Sample only the head and tail.
+    #[default]
+    None,
+
+    /// Sample `count` windows of `size` bytes, spaced evenly across the interior.
+    Even { count: u32, size: u64 },
+
+    /// Sample `count` windows of `size` bytes at positions derived from the content length.
+    ///
+    /// Content of the same length is always sampled at the same positions, but those positions
+    /// are not regularly spaced.
+    Derived { count: u32, size: u64 },
+}
+
+impl Sampling {
+    pub fn head_size(&self) -> u64 {
+        self.head
+    }
+
+    pub fn tail_size(&self) -> u64 {
+        self.tail
+    }
+
+    pub fn interior(&self) -> Interior {
+        self.interior
+    }
+
+    pub(crate) fn head_len(&self, len: u64) -> u64 {
+        len.min(self.head)
+    }
+
+    pub(crate) fn tail_len(&self, len: u64) -> u64 {
+        len.saturating_sub(self.head).min(self.tail)
+    }
+
+    /// The largest single region this sampling will read.
+    pub(crate) fn max_window(&self) -> u64 {
+        let interior = match self.interior {
+            Interior::None => 0,
+            Interior::Even { size, .. } | Interior::Derived { size, .. } => size,
+        };
+        self.head.max(self.tail).max(interior)
+    }
+
+    /// The number of interior windows sampled from content of the given length.
+    pub(crate) fn interior_count(&self, len: u64) -> usize {
+        let region = len - self.head_len(len) - self.tail_len(len);
+        match self.interior {
+            Interior::None => 0,
+            Interior::Even { count, size } | Interior::Derived { count, size } => {
+                if size.min(region) == 0 {
+                    0
+                } else {
+                    count as usize
+                }
+            }
+        }
+    }
+
+    /// The windows sampled from content of the given length, in the order their hashes are
+    /// assembled into an imprint: the head, the tail if there is one, and the interior windows in
+    /// ascending order.
+    pub(crate) fn windows(&self, len: u64, scheme: &Scheme) -> Vec<(Role, Range<u64>)> {
+        let mut windows = vec![(Role::Head, 0..self.head_len(len))];
+        let tail_len = self.tail_len(len);
+        if tail_len > 0 {
+            windows.push((Role::Tail, len - tail_len..len));
+        }
+        windows.extend(
+            self.interior_windows(len, scheme)
+                .into_iter()
+                .map(|window| (Role::Interior, window)),
+        );
+        windows
+    }
+
+    /// Byte ranges of the interior windows for content of the given length, in ascending order.
+    ///
+    /// Derived positions depend on the hashing scheme, so keyed imprints sample positions that
+    /// cannot be predicted without the key.
+    pub(crate) fn interior_windows(&self, len: u64, scheme: &Scheme) -> Vec<Range<u64>> {
+        let start = self.head_len(len);
+        let end = len - self.tail_len(len);
+        let region = end - start;
+
+        let (count, size) = match self.interior {
+            Interior::None => return Vec::new(),
+            Interior::Even { count, size } | Interior::Derived { count, size } => {
+                (count as u64, size.min(region))
+            }
+        };
+
+        if count == 0 || size == 0 {
+            return Vec::new();
+        }
+
+        // Windows may start anywhere in `0..=span` relative to the start of the interior.
+        let span = region - size;
+        let mut offsets: Vec<u64> = match self.interior {
+            Interior::None => unreachable!(),
+            Interior::Even { .. } => (0..count)
+                .map(|i| (span as u128 * (2 * i as u128 + 1) / (2 * count as u128)) as u64)
+                .collect(),
+            Interior::Derived { .. } => {
+                let mut hasher = scheme.position_hasher();
+                hasher.update(&len.to_le_bytes());
+                let mut positions = hasher.finalize_xof();
+                (0..count)
+                    .map(|_| {
+                        let mut bytes = [0; 8];
+                        positions.fill(&mut bytes);
+                        u64::from_le_bytes(bytes) % (span + 1)
+                    })
+                    .collect()
+            }
+        };
+
+        offsets.sort_unstable();
+        offsets
+            .into_iter()
+            .map(|offset| start + offset..start + offset + size)
+            .collect()
+    }
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Sampling {
+            head: SAMPLE_SIZE,
+            tail: SAMPLE_SIZE,
+            interior: Interior::None,
+        }
+    }
+}
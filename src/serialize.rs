@@ -0,0 +1,109 @@
+use std::fmt;
+
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{Algorithm, Imprint, KeyId, SampleHash, Sampling, Version};
+
+/// The form taken by an imprint in human-readable formats, with hashes written as hex.
+///
+/// Compact formats use the binary encoding from `Imprint::to_bytes` instead.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Imprint")]
+struct Readable {
+    len: u64,
+    sampling: Sampling,
+    #[serde(default = "legacy")]
+    version: Version,
+    #[serde(default)]
+    algorithm: Algorithm,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key_id: Option<String>,
+    head: String,
+    tail: Option<String>,
+    interior: Vec<String>,
+}
+
+impl Serialize for Imprint {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return serializer.serialize_bytes(&self.to_bytes());
+        }
+
+        Readable {
+            len: self.len,
+            sampling: self.sampling,
+            version: self.version,
+            algorithm: self.algorithm,
+            key_id: self.key_id.map(|key_id| key_id.to_string()),
+            head: self.head.to_string(),
+            tail: self.tail.map(|tail| tail.to_string()),
+            interior: self.interior.iter().map(|h| h.to_string()).collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Imprint {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            return deserializer.deserialize_bytes(BytesVisitor);
+        }
+
+        let readable = Readable::deserialize(deserializer)?;
+        let algorithm = readable.algorithm;
+        let hash = |hex: &str| {
+            SampleHash::from_hex(hex, algorithm).ok_or_else(|| de::Error::custom("invalid hash"))
+        };
+        let imprint = Imprint {
+            len: readable.len,
+            sampling: readable.sampling,
+            version: readable.version,
+            algorithm,
+            key_id: readable
+                .key_id
+                .as_deref()
+                .map(|hex| KeyId::from_hex(hex).ok_or_else(|| de::Error::custom("invalid key id")))
+                .transpose()?,
+            head: hash(&readable.head)?,
+            tail: readable.tail.as_deref().map(hash).transpose()?,
+            interior: readable
+                .interior
+                .iter()
+                .map(|hex| hash(hex))
+                .collect::<Result<_, _>>()?,
+        };
+
+        imprint.validate().map_err(de::Error::custom)?;
+        Ok(imprint)
+    }
+}
+
+/// Imprints serialized before versioned hashing have no version, and were hashed with plain BLAKE3.
+fn legacy() -> Version {
+    Version::Legacy
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Imprint;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an encoded imprint")
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Imprint, E> {
+        Imprint::from_bytes(bytes).map_err(E::custom)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Imprint, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or_default().min(4096));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        self.visit_bytes(&bytes)
+    }
+}
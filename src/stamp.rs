@@ -0,0 +1,204 @@
+use std::{
+    convert::TryInto,
+    fs::Metadata,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::ImprintError;
+
+const TIME_NONE: u8 = 0;
+const TIME_AFTER: u8 = 1;
+const TIME_BEFORE: u8 = 2;
+
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+
+/// Files modified this recently before imprinting began are not worth remembering, since a
+/// further modification within the granularity of the filesystem's timestamps would leave their
+/// stamp unchanged.
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// The attributes of a file that change when its content is modified.
+///
+/// On Unix, the device, inode and change time are included, so a file replaced or rewritten
+/// in place is detected even if its length and modification time are preserved.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub(crate) struct Stamp {
+    pub(crate) len: u64,
+    pub(crate) modified: Option<SystemTime>,
+    #[cfg(unix)]
+    pub(crate) dev: u64,
+    #[cfg(unix)]
+    pub(crate) ino: u64,
+    #[cfg(unix)]
+    pub(crate) ctime: (i64, i64),
+}
+
+impl Stamp {
+    pub(crate) fn new(meta: &Metadata) -> Self {
+        #[cfg(unix)]
+        use std::os::unix::fs::MetadataExt;
+
+        Stamp {
+            len: meta.len(),
+            modified: meta.modified().ok(),
+            #[cfg(unix)]
+            dev: meta.dev(),
+            #[cfg(unix)]
+            ino: meta.ino(),
+            #[cfg(unix)]
+            ctime: (meta.ctime(), meta.ctime_nsec()),
+        }
+    }
+
+    /// Returns an error if the file changed between the two stamps.
+    pub(crate) fn check(&self, after: &Stamp) -> Result<(), ImprintError> {
+        if self.len != after.len {
+            return Err(ImprintError::SizeChanged {
+                path: None,
+                expected: self.len,
+                actual: after.len,
+            });
+        }
+
+        if self != after {
+            return Err(ImprintError::Unstable { path: None });
+        }
+
+        Ok(())
+    }
+
+    /// True if the file was modified so shortly before `started` that a later modification
+    /// might not change its stamp.
+    pub(crate) fn is_racy(&self, started: SystemTime) -> bool {
+        match self.modified {
+            Some(modified) => started
+                .duration_since(modified)
+                .map_or(true, |age| age < RACY_WINDOW),
+            None => true,
+        }
+    }
+}
+
+/// Encodes a time as a tag, then the seconds and nanoseconds from the epoch. The tag records
+/// whether the time is missing, after the epoch or before it.
+pub(crate) fn encode_time(buf: &mut Vec<u8>, time: Option<SystemTime>) {
+    let (tag, offset) = match time.map(|time| time.duration_since(UNIX_EPOCH)) {
+        None => {
+            buf.push(TIME_NONE);
+            return;
+        }
+        Some(Ok(after)) => (TIME_AFTER, after),
+        Some(Err(before)) => (TIME_BEFORE, before.duration()),
+    };
+    buf.push(tag);
+    buf.extend_from_slice(&offset.as_secs().to_le_bytes());
+    buf.extend_from_slice(&offset.subsec_nanos().to_le_bytes());
+}
+
+pub(crate) fn decode_time(body: &mut &[u8]) -> Option<Option<SystemTime>> {
+    let (&tag, rest) = body.split_first()?;
+    *body = rest;
+    if tag == TIME_NONE {
+        return Some(None);
+    }
+
+    // The value may have been written by anyone, so an offset that does not fit is rejected
+    // rather than allowed to overflow.
+    let secs = u64::from_le_bytes(take(body)?);
+    let nanos = u32::from_le_bytes(take(body)?);
+    if nanos >= NANOS_PER_SEC {
+        return None;
+    }
+    let offset = Duration::from_secs(secs).checked_add(Duration::from_nanos(nanos as u64))?;
+    match tag {
+        TIME_AFTER => UNIX_EPOCH.checked_add(offset).map(Some),
+        TIME_BEFORE => UNIX_EPOCH.checked_sub(offset).map(Some),
+        _ => None,
+    }
+}
+
+/// Takes the next `N` bytes of a body being decoded.
+pub(crate) fn take<const N: usize>(body: &mut &[u8]) -> Option<[u8; N]> {
+    let bytes = body.get(..N)?.try_into().ok()?;
+    *body = &body[N..];
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn decode(mut bytes: &[u8]) -> Option<Option<SystemTime>> {
+        decode_time(&mut bytes)
+    }
+
+    fn encoded(tag: u8, secs: u64, nanos: u32) -> Vec<u8> {
+        let mut buf = vec![tag];
+        buf.extend_from_slice(&secs.to_le_bytes());
+        buf.extend_from_slice(&nanos.to_le_bytes());
+        buf
+    }
+
+    #[test]
+    fn times_round_trip() {
+        let times = [
+            None,
+            Some(UNIX_EPOCH),
+            Some(UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789)),
+            Some(UNIX_EPOCH - Duration::new(86_400, 1)),
+        ];
+        for time in times {
+            let mut buf = Vec::new();
+            encode_time(&mut buf, time);
+            assert_eq!(decode(&buf), Some(time));
+        }
+    }
+
+    #[test]
+    fn out_of_range_times_are_rejected() {
+        assert_eq!(decode(&encoded(TIME_AFTER, u64::MAX, u32::MAX)), None);
+        assert_eq!(decode(&encoded(TIME_AFTER, 0, NANOS_PER_SEC)), None);
+        assert_eq!(decode(&encoded(TIME_BEFORE, u64::MAX, 999_999_999)), None);
+        assert_eq!(decode(&encoded(3, 0, 0)), None);
+        assert_eq!(decode(&[TIME_AFTER, 0, 0]), None);
+    }
+
+    #[test]
+    fn changes_between_stamps_are_detected() {
+        let before = Stamp::new(&fs::metadata(std::env::current_exe().unwrap()).unwrap());
+        assert!(before.check(&before).is_ok());
+
+        let grown = Stamp {
+            len: before.len + 1,
+            ..before
+        };
+        assert!(matches!(
+            before.check(&grown),
+            Err(ImprintError::SizeChanged { expected, actual, .. })
+                if expected == before.len && actual == grown.len
+        ));
+
+        let touched = Stamp {
+            modified: Some(UNIX_EPOCH),
+            ..before
+        };
+        assert!(matches!(
+            before.check(&touched),
+            Err(ImprintError::Unstable { .. })
+        ));
+
+        #[cfg(unix)]
+        {
+            let replaced = Stamp {
+                ino: before.ino + 1,
+                ..before
+            };
+            assert!(matches!(
+                before.check(&replaced),
+                Err(ImprintError::Unstable { .. })
+            ));
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::{convert::TryFrom, ops::Range};
+
+use crate::{
+    digest::{Digest, Sampler},
+    hashing::{Role, Scheme},
+    Imprint, ImprintError, Interior, Sampling,
+};
+
+/// Incrementally computes an imprint from content supplied in order.
+///
+/// The head is hashed as bytes arrive, and the bytes following the head are retained in a ring
+/// buffer so the tail can be hashed once the content ends. The buffer grows as bytes arrive, up
+/// to the size of the tail sample, or of the tail of the expected length if it is known. Interior
+/// windows depend on the length of the content, so interior sampling requires the length to be
+/// known in advance.
+#[derive(Clone, Debug)]
+pub(crate) struct Stream {
+    sampling: Sampling,
+    scheme: Scheme,
+    expected: Option<u64>,
+    pos: u64,
+    head: Sampler,
+    tail: Ring,
+    interior: Vec<(Range<u64>, Sampler)>,
+}
+
+impl Stream {
+    pub(crate) fn new(
+        sampling: Sampling,
+        scheme: Scheme,
+        len: Option<u64>,
+    ) -> Result<Self, ImprintError> {
+        let interior = match (sampling.interior, len) {
+            (Interior::None, _) => Vec::new(),
+            (_, Some(len)) => sampling
+                .interior_windows(len, &scheme)
+                .into_iter()
+                .map(|window| (window, scheme.hasher(Role::Interior)))
+                .collect(),
+            (_, None) => return Err(ImprintError::LengthRequired),
+        };
+
+        let tail = match len {
+            Some(len) => sampling.tail_len(len),
+            None => sampling.tail,
+        };
+
+        Ok(Stream {
+            sampling,
+            scheme,
+            expected: len,
+            pos: 0,
+            head: scheme.hasher(Role::Head),
+            tail: Ring::new(usize::try_from(tail).unwrap_or(usize::MAX)),
+            interior,
+        })
+    }
+
+    pub(crate) fn update(&mut self, buf: &[u8]) {
+        let start = self.pos;
+        let end = start + buf.len() as u64;
+
+        if start < self.sampling.head {
+            let split = (self.sampling.head - start).min(buf.len() as u64) as usize;
+            self.head.update(&buf[..split]);
+            self.tail.push(&buf[split..]);
+        } else {
+            self.tail.push(buf);
+        }
+
+        for (window, hasher) in &mut self.interior {
+            let from = window.start.max(start);
+            let to = window.end.min(end);
+            if from < to {
+                hasher.update(&buf[(from - start) as usize..(to - start) as usize]);
+            }
+        }
+
+        self.pos = end;
+    }
+
+    pub(crate) fn finish(&self) -> Result<Imprint, ImprintError> {
+        let len = self.pos;
+        match self.expected {
+            Some(expected) if len < expected => {
+                return Err(ImprintError::Truncated {
+                    path: None,
+                    expected,
+                })
+            }
+            Some(expected) if len > expected => {
+                return Err(ImprintError::SizeChanged {
+                    path: None,
+                    expected,
+                    actual: len,
+                })
+            }
+            _ => (),
+        }
+
+        Ok(Imprint {
+            len,
+            sampling: self.sampling,
+            version: self.scheme.version,
+            algorithm: self.scheme.algorithm,
+            key_id: self.scheme.key_id(),
+            head: self.head.clone().finalize(),
+            tail: (self.sampling.tail_len(len) > 0)
+                .then(|| self.tail.hash(self.scheme.hasher(Role::Tail))),
+            interior: self
+                .interior
+                .iter()
+                .map(|(_, hasher)| hasher.clone().finalize())
+                .collect(),
+        })
+    }
+}
+
+/// Retains the most recent bytes pushed into it, up to its capacity.
+///
+/// The buffer is only allocated as bytes arrive, so a large capacity costs nothing for short
+/// content.
+#[derive(Clone, Debug)]
+struct Ring {
+    buf: Vec<u8>,
+    capacity: usize,
+    /// Where the oldest byte is, once the buffer is full.
+    end: usize,
+}
+
+impl Ring {
+    fn new(capacity: usize) -> Self {
+        Ring {
+            buf: Vec::new(),
+            capacity,
+            end: 0,
+        }
+    }
+
+    fn push(&mut self, mut data: &[u8]) {
+        let capacity = self.capacity;
+        if data.len() >= capacity {
+            data = &data[data.len() - capacity..];
+        }
+
+        if self.buf.len() < capacity {
+            let fill = (capacity - self.buf.len()).min(data.len());
+            self.buf.extend_from_slice(&data[..fill]);
+            data = &data[fill..];
+        }
+
+        let first = (capacity - self.end).min(data.len());
+        self.buf[self.end..self.end + first].copy_from_slice(&data[..first]);
+        self.buf[..data.len() - first].copy_from_slice(&data[first..]);
+        if capacity > 0 {
+            self.end = (self.end + data.len()) % capacity;
+        }
+    }
+
+    fn hash<D: Digest>(&self, mut hasher: D) -> D::Output {
+        hasher.update(&self.buf[self.end..]);
+        hasher.update(&self.buf[..self.end]);
+        hasher.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, Write};
+
+    use crate::{Imprinter, SAMPLE_SIZE};
+
+    use super::*;
+
+    /// Reads at most a fixed number of bytes at a time.
+    struct Chunked<'a> {
+        data: &'a [u8],
+        size: usize,
+    }
+
+    impl Read for Chunked<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.size).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    fn content(len: u64) -> Vec<u8> {
+        (0..len).map(|i| (i * 31 + i / 509) as u8).collect()
+    }
+
+    fn check(imprinter: &mut Imprinter, content: &[u8]) {
+        let len = content.len() as u64;
+        let expected = imprinter.imprint_memory(content).unwrap();
+        let interior = imprinter.sampling().interior() != Interior::None;
+
+        for size in [7, 4099, 0x30001] {
+            let context = format!("length {}, chunks of {}", len, size);
+            let reader = Chunked {
+                data: content,
+                size,
+            };
+            let streamed = imprinter.imprint_stream(reader, Some(len)).unwrap();
+            assert_eq!(streamed, expected, "stream, {}", context);
+
+            let mut writer = imprinter.writer(io::sink(), Some(len)).unwrap();
+            for chunk in content.chunks(size) {
+                writer.write_all(chunk).unwrap();
+            }
+            assert_eq!(writer.imprint().unwrap(), expected, "writer, {}", context);
+
+            if !interior {
+                let reader = Chunked {
+                    data: content,
+                    size,
+                };
+                let streamed = imprinter.imprint_stream(reader, None).unwrap();
+                assert_eq!(streamed, expected, "stream without length, {}", context);
+            }
+        }
+    }
+
+    #[test]
+    fn streams_match_memory() {
+        let interiors = [
+            Interior::None,
+            Interior::Even {
+                count: 4,
+                size: 1000,
+            },
+            Interior::Derived {
+                count: 3,
+                size: 777,
+            },
+        ];
+        let sizes = [(SAMPLE_SIZE, SAMPLE_SIZE), (1000, 333)];
+
+        for interior in interiors {
+            for (head, tail) in sizes {
+                let mut imprinter = Imprinter::new()
+                    .head_size(head)
+                    .tail_size(tail)
+                    .interior(interior);
+                let lens = [
+                    0,
+                    head - 1,
+                    head,
+                    head + 1,
+                    head + tail,
+                    head + tail + 1,
+                    3 * (head + tail) + 5,
+                ];
+                for len in lens {
+                    check(&mut imprinter, &content(len));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tail_buffer_is_sized_to_the_content() {
+        let mut imprinter = Imprinter::new().tail_size(1 << 44);
+        let expected = imprinter.imprint_memory(b"tiny content").unwrap();
+        let content = &b"tiny content"[..];
+        assert_eq!(
+            imprinter.imprint_stream(content, Some(12)).unwrap(),
+            expected
+        );
+        assert_eq!(imprinter.imprint_stream(content, None).unwrap(), expected);
+
+        let stream = Stream::new(imprinter.sampling(), imprinter.scheme(), Some(12)).unwrap();
+        assert_eq!(stream.tail.capacity, 0);
+    }
+
+    #[test]
+    fn interior_sampling_requires_length() {
+        let mut imprinter = Imprinter::new().interior(Interior::Even { count: 1, size: 1 });
+        let result = imprinter.imprint_stream(&b"content"[..], None);
+        assert!(matches!(result, Err(ImprintError::LengthRequired)));
+    }
+}
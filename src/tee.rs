@@ -0,0 +1,129 @@
+use std::io::{self, Read, Write};
+
+use crate::{stream::Stream, Imprint, ImprintError, Imprinter};
+
+/// A writer that imprints everything written through it.
+///
+/// The resulting imprint is identical to imprinting the written bytes from a file.
+#[derive(Debug)]
+pub struct ImprintWriter<W> {
+    inner: W,
+    stream: Stream,
+}
+
+/// A reader that imprints everything read through it.
+///
+/// The resulting imprint covers only the bytes actually read, so read to the end before
+/// finishing if the imprint should cover the entire content.
+#[derive(Debug)]
+pub struct ImprintReader<R> {
+    inner: R,
+    stream: Stream,
+}
+
+impl<W: Write> ImprintWriter<W> {
+    /// Wraps a writer, imprinting with default sampling.
+    pub fn new(inner: W) -> Self {
+        let stream = Stream::new(Default::default(), Default::default(), None)
+            .expect("default sampling does not require a length");
+        ImprintWriter { inner, stream }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Imprints the bytes written so far.
+    pub fn imprint(&self) -> Result<Imprint, ImprintError> {
+        self.stream.finish()
+    }
+
+    /// Flushes the inner writer and returns it along with the imprint of everything written.
+    pub fn finish(mut self) -> Result<(W, Imprint), ImprintError> {
+        self.inner.flush()?;
+        let imprint = self.stream.finish()?;
+        Ok((self.inner, imprint))
+    }
+}
+
+impl<W: Write> Write for ImprintWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.stream.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: Read> ImprintReader<R> {
+    /// Wraps a reader, imprinting with default sampling.
+    pub fn new(inner: R) -> Self {
+        let stream = Stream::new(Default::default(), Default::default(), None)
+            .expect("default sampling does not require a length");
+        ImprintReader { inner, stream }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Imprints the bytes read so far.
+    pub fn imprint(&self) -> Result<Imprint, ImprintError> {
+        self.stream.finish()
+    }
+
+    /// Returns the inner reader along with the imprint of everything read.
+    pub fn finish(self) -> Result<(R, Imprint), ImprintError> {
+        let imprint = self.stream.finish()?;
+        Ok((self.inner, imprint))
+    }
+}
+
+impl<R: Read> Read for ImprintReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.stream.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl Imprinter {
+    /// Wraps a writer, imprinting with this imprinter's sampling.
+    ///
+    /// Interior sampling requires `len`; if given, exactly that many bytes must be written.
+    pub fn writer<W: Write>(
+        &self,
+        inner: W,
+        len: Option<u64>,
+    ) -> Result<ImprintWriter<W>, ImprintError> {
+        Ok(ImprintWriter {
+            inner,
+            stream: Stream::new(self.sampling(), self.scheme(), len)?,
+        })
+    }
+
+    /// Wraps a reader, imprinting with this imprinter's sampling.
+    ///
+    /// Interior sampling requires `len`; if given, exactly that many bytes must be read.
+    pub fn reader<R: Read>(
+        &self,
+        inner: R,
+        len: Option<u64>,
+    ) -> Result<ImprintReader<R>, ImprintError> {
+        Ok(ImprintReader {
+            inner,
+            stream: Stream::new(self.sampling(), self.scheme(), len)?,
+        })
+    }
+}
@@ -0,0 +1,224 @@
+use std::{fmt, str::FromStr};
+
+use crate::{Algorithm, DecodeError, Imprint, Interior, KeyId, SampleHash, Sampling, Version};
+
+/// The tag that begins the canonical text form of an imprint.
+const TAG: &str = "imp2";
+
+/// The tag that began the text form before versioned hashing, which omitted the version.
+const LEGACY_TAG: &str = "imp1";
+
+impl Imprint {
+    /// Writes the canonical text form of the imprint.
+    ///
+    /// The form is a dot-separated list of the format tag, the hashing version, the length, the
+    /// sampling parameters, and the head, tail and interior hashes in hex. The version is
+    /// followed by `:` and the algorithm unless the samples were hashed with BLAKE3, and then by
+    /// `+` and the key identifier if the imprint is keyed. For example:
+    ///
+    /// ```text
+    /// imp2.v1.700000.131072-131072-e2x4096.<head>.<tail>.<interior>.<interior>
+    /// ```
+    ///
+    /// Interior sampling is written as `e<count>x<size>` for even spacing and `d<count>x<size>`
+    /// for derived positions, and is omitted when only the head and tail are sampled.
+    pub(crate) fn write_canonical(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = match self.version {
+            Version::Legacy => "legacy",
+            Version::V1 => "v1",
+        };
+        write!(f, "{}.{}", TAG, version)?;
+        if self.algorithm != Algorithm::Blake3 {
+            write!(f, ":{}", self.algorithm.name())?;
+        }
+        if let Some(key_id) = &self.key_id {
+            write!(f, "+{}", key_id)?;
+        }
+        write!(
+            f,
+            ".{}.{}-{}",
+            self.len, self.sampling.head, self.sampling.tail
+        )?;
+
+        match self.sampling.interior {
+            Interior::None => (),
+            Interior::Even { count, size } => write!(f, "-e{}x{}", count, size)?,
+            Interior::Derived { count, size } => write!(f, "-d{}x{}", count, size)?,
+        }
+
+        write!(f, ".{}", self.head)?;
+        for hash in self.tail.iter().chain(self.interior.iter()) {
+            write!(f, ".{}", hash)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Imprint {
+    type Err = DecodeError;
+
+    /// Parses the canonical text form written by `{:#}`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split('.');
+        let (version, algorithm, key_id) = match fields.next() {
+            Some(TAG) => version(fields.next().ok_or(DecodeError::Malformed)?)?,
+            Some(LEGACY_TAG) => (Version::Legacy, Algorithm::Blake3, None),
+            _ => return Err(DecodeError::Malformed),
+        };
+
+        let len = number(fields.next())?;
+        let sampling = sampling(fields.next().ok_or(DecodeError::Malformed)?)?;
+        let head = hash(fields.next(), algorithm)?;
+        let tail = if sampling.tail_len(len) > 0 {
+            Some(hash(fields.next(), algorithm)?)
+        } else {
+            None
+        };
+
+        let interior = fields
+            .map(|hex| hash(Some(hex), algorithm))
+            .collect::<Result<_, _>>()?;
+        let imprint = Imprint {
+            len,
+            sampling,
+            version,
+            algorithm,
+            key_id,
+            head,
+            tail,
+            interior,
+        };
+
+        imprint.validate()?;
+        Ok(imprint)
+    }
+}
+
+fn version(s: &str) -> Result<(Version, Algorithm, Option<KeyId>), DecodeError> {
+    let (s, key_id) = match s.split_once('+') {
+        Some((s, key_id)) => (
+            s,
+            Some(KeyId::from_hex(key_id).ok_or(DecodeError::Malformed)?),
+        ),
+        None => (s, None),
+    };
+
+    let (version, algorithm) = match s.split_once(':') {
+        Some((version, name)) => (version, Algorithm::from_name(name)?),
+        None => (s, Algorithm::Blake3),
+    };
+
+    match version {
+        "legacy" => Ok((Version::Legacy, algorithm, key_id)),
+        "v1" => Ok((Version::V1, algorithm, key_id)),
+        _ => Err(DecodeError::Malformed),
+    }
+}
+
+fn sampling(s: &str) -> Result<Sampling, DecodeError> {
+    let mut parts = s.split('-');
+    let head = number(parts.next())?;
+    let tail = number(parts.next())?;
+
+    let interior = match parts.next() {
+        None => Interior::None,
+        Some(part) => {
+            let (count, size) = part
+                .get(1..)
+                .and_then(|params| params.split_once('x'))
+                .ok_or(DecodeError::Malformed)?;
+            let count = number(Some(count))?;
+            let size = number(Some(size))?;
+            match part.as_bytes()[0] {
+                b'e' => Interior::Even { count, size },
+                b'd' => Interior::Derived { count, size },
+                _ => return Err(DecodeError::Malformed),
+            }
+        }
+    };
+
+    if parts.next().is_some() {
+        return Err(DecodeError::Malformed);
+    }
+
+    Ok(Sampling {
+        head,
+        tail,
+        interior,
+    })
+}
+
+fn number<T: FromStr>(s: Option<&str>) -> Result<T, DecodeError> {
+    match s {
+        Some(s) if s.bytes().all(|b| b.is_ascii_digit()) => {
+            s.parse().map_err(|_| DecodeError::Malformed)
+        }
+        _ => Err(DecodeError::Malformed),
+    }
+}
+
+fn hash(s: Option<&str>, algorithm: Algorithm) -> Result<SampleHash, DecodeError> {
+    s.and_then(|s| SampleHash::from_hex(s, algorithm))
+        .ok_or(DecodeError::Malformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        encoding::tests::{fixture_imprint, imprints},
+        Imprinter,
+    };
+
+    use super::*;
+
+    #[test]
+    fn text_round_trips() {
+        for imprint in imprints() {
+            let text = format!("{:#}", imprint);
+            assert_eq!(text.parse(), Ok(imprint), "{}", text);
+        }
+    }
+
+    #[test]
+    fn imp1_text_parses() {
+        let text = include_str!("../tests/fixtures/imprint-v1.txt");
+        let imprint: Imprint = text.trim_end().parse().unwrap();
+        assert_eq!(imprint, fixture_imprint());
+        assert!(format!("{:#}", imprint).starts_with("imp2.legacy."));
+    }
+
+    #[test]
+    fn malformed_text_is_rejected() {
+        let text = format!("{:#}", fixture_imprint());
+        let (without_last, _) = text.rsplit_once('.').unwrap();
+        for s in ["", "imp9.1.1-1.00", without_last, &text[..text.len() - 1]] {
+            assert!(s.parse::<Imprint>().is_err(), "{}", s);
+        }
+        assert!(format!("{}.00", text).parse::<Imprint>().is_err());
+
+        // Hex is accepted only in the lowercase, unsigned form that is written.
+        let head = fixture_imprint().head().to_string();
+        let respelled = [
+            head.to_ascii_uppercase(),
+            format!("+{}", &head[1..]),
+            format!("{}+{}", &head[..2], &head[3..]),
+        ];
+        for hex in &respelled {
+            let s = text.replace(&head, hex);
+            assert!(s.parse::<Imprint>().is_err(), "{}", s);
+        }
+
+        let keyed = format!(
+            "{:#}",
+            Imprinter::new().key([7; 32]).imprint_memory(b"").unwrap()
+        );
+        assert!(keyed.parse::<Imprint>().is_ok());
+        let key_id = keyed.split(['.', '+']).nth(2).unwrap();
+        let uppercase = keyed.replacen(key_id, &key_id.to_ascii_uppercase(), 1);
+        let signed = keyed.replacen(key_id, &format!("+{}", &key_id[1..]), 1);
+        for s in [uppercase, signed] {
+            assert!(s.parse::<Imprint>().is_err(), "{}", s);
+        }
+    }
+}
@@ -0,0 +1,468 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    ffi::OsString,
+    fmt,
+    fs::Metadata,
+    io::{self, ErrorKind},
+    mem,
+    ops::{Bound, RangeBounds},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use globset::{Glob, GlobMatcher};
+use ignore::WalkBuilder;
+
+use crate::{Imprint, ImprintError, Imprinter, Manifest, Verification};
+
+/// Recursively imprints the regular files under one or more roots.
+///
+/// By default, every regular file is imprinted: symbolic links are not followed, and neither
+/// hidden files nor ignore files are given special treatment.
+#[derive(Clone, Debug)]
+pub struct Walker {
+    roots: Vec<PathBuf>,
+    imprinter: Imprinter,
+    follow_links: bool,
+    same_file_system: bool,
+    gitignore: bool,
+    ignore_files: Vec<OsString>,
+    include: Vec<GlobMatcher>,
+    exclude: Vec<GlobMatcher>,
+    size: (Bound<u64>, Bound<u64>),
+    modified: (Bound<SystemTime>, Bound<SystemTime>),
+}
+
+/// A regular file found by a walk, together with its imprint.
+#[derive(Clone, Debug)]
+pub struct WalkEntry {
+    pub path: PathBuf,
+    pub metadata: Metadata,
+    pub imprint: Imprint,
+}
+
+/// An iterator over the files found by a `Walker`, with their imprints.
+///
+/// An entry that cannot be read or imprinted is yielded as an error, and the walk continues.
+pub struct Walk {
+    files: Files,
+    imprinter: Imprinter,
+}
+
+/// An iterator over the paths and metadata of the files found by a `Walker`, without imprinting
+/// them.
+pub struct Files {
+    inner: ignore::Walk,
+    roots: Arc<[PathBuf]>,
+    include: Vec<GlobMatcher>,
+    size: (Bound<u64>, Bound<u64>),
+    modified: (Bound<SystemTime>, Bound<SystemTime>),
+}
+
+impl Walker {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Walker {
+            roots: vec![root.into()],
+            imprinter: Imprinter::new(),
+            follow_links: false,
+            same_file_system: false,
+            gitignore: false,
+            ignore_files: Vec::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            size: (Bound::Unbounded, Bound::Unbounded),
+            modified: (Bound::Unbounded, Bound::Unbounded),
+        }
+    }
+
+    /// Adds another root to walk.
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.roots.push(root.into());
+        self
+    }
+
+    /// Sets the imprinter used to imprint each file.
+    pub fn imprinter(mut self, imprinter: Imprinter) -> Self {
+        self.imprinter = imprinter;
+        self
+    }
+
+    /// Sets whether symbolic links are followed. Unfollowed links are skipped.
+    pub fn follow_links(mut self, yes: bool) -> Self {
+        self.follow_links = yes;
+        self
+    }
+
+    /// Sets whether the walk is kept to the filesystem of the root it started from.
+    pub fn same_file_system(mut self, yes: bool) -> Self {
+        self.same_file_system = yes;
+        self
+    }
+
+    /// Sets whether `.gitignore` files, the repository's exclude file and the global gitignore
+    /// are honored.
+    ///
+    /// `.gitignore` files are honored whether or not they are inside a git repository.
+    pub fn gitignore(mut self, yes: bool) -> Self {
+        self.gitignore = yes;
+        self
+    }
+
+    /// Honors ignore files with the given name, which use the same syntax as `.gitignore`.
+    pub fn ignore_file(mut self, name: impl Into<OsString>) -> Self {
+        self.ignore_files.push(name.into());
+        self
+    }
+
+    /// Imprints only files matching the glob, or any other included glob.
+    ///
+    /// Globs are matched against paths relative to the root being walked.
+    pub fn include(mut self, glob: Glob) -> Self {
+        self.include.push(glob.compile_matcher());
+        self
+    }
+
+    /// Skips files and directories matching the glob.
+    ///
+    /// Globs are matched against paths relative to the root being walked. Excluded directories
+    /// are not descended into.
+    pub fn exclude(mut self, glob: Glob) -> Self {
+        self.exclude.push(glob.compile_matcher());
+        self
+    }
+
+    /// Imprints only files whose length, in bytes, falls within the range.
+    pub fn size(mut self, range: impl RangeBounds<u64>) -> Self {
+        self.size = (range.start_bound().cloned(), range.end_bound().cloned());
+        self
+    }
+
+    /// Imprints only files last modified within the range.
+    pub fn modified(mut self, range: impl RangeBounds<SystemTime>) -> Self {
+        self.modified = (range.start_bound().cloned(), range.end_bound().cloned());
+        self
+    }
+
+    pub fn walk(mut self) -> Walk {
+        let imprinter = mem::take(&mut self.imprinter);
+        Walk {
+            files: self.files(),
+            imprinter,
+        }
+    }
+
+    /// Imprints the files found into a manifest, keyed by their paths relative to the root they
+    /// were found under.
+    ///
+    /// Files that cannot be read or imprinted are left out of the manifest and returned as
+    /// errors. When files under different roots share a relative path, the first one found is
+    /// recorded and each of the others is returned as an error.
+    pub fn snapshot(self) -> (Manifest, Vec<ImprintError>) {
+        let roots: Arc<[PathBuf]> = self.roots.clone().into();
+        let mut manifest = Manifest::new();
+        let mut errors = Vec::new();
+        for entry in self.walk() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                }
+            };
+
+            let relative = relative(&roots, &entry.path);
+            if manifest.get(relative).is_some() {
+                errors.push(collision(&entry.path, relative));
+            } else {
+                manifest.insert(relative, entry.imprint);
+            }
+        }
+        (manifest, errors)
+    }
+
+    /// Verifies the files found against a manifest made by `snapshot`.
+    ///
+    /// Files are matched to entries by their paths relative to the root they were found under.
+    /// The walker should apply the same filters as the one that made the manifest, since an
+    /// entry for a file it skips is reported as missing. Keyed imprints are verified with the
+    /// walker's imprinter, which must hold the same key. As in `snapshot`, only the first file
+    /// found at each relative path is verified, and the others are reported as errors.
+    pub fn verify(mut self, manifest: &Manifest) -> Verification {
+        let imprinter = mem::take(&mut self.imprinter);
+        let roots: Arc<[PathBuf]> = self.roots.clone().into();
+        let mut expected: BTreeMap<&Path, &Imprint> = manifest.iter().collect();
+        let mut seen = HashSet::new();
+        let mut verification = Verification::default();
+
+        for file in self.files() {
+            let path = match file {
+                Ok((path, _)) => path,
+                Err(e) => {
+                    verification.errors.push(e);
+                    continue;
+                }
+            };
+
+            let relative = relative(&roots, &path);
+            if !seen.insert(relative.to_path_buf()) {
+                verification.errors.push(collision(&path, relative));
+                continue;
+            }
+
+            let imprint = match expected.remove(relative) {
+                Some(imprint) => imprint,
+                None => {
+                    verification.added.push(relative.into());
+                    continue;
+                }
+            };
+
+            match imprinter.verify(imprint, &path) {
+                Ok(true) => verification.unchanged.push(relative.into()),
+                Ok(false) => verification.modified.push(relative.into()),
+                Err(e) => verification.errors.push(e),
+            }
+        }
+
+        verification.missing = expected.into_keys().map(PathBuf::from).collect();
+        verification.unchanged.sort();
+        verification.modified.sort();
+        verification.added.sort();
+        verification
+    }
+
+    /// Walks without imprinting, yielding the path and metadata of each file.
+    pub fn files(self) -> Files {
+        let roots: Arc<[PathBuf]> = self.roots.into();
+        let mut builder = WalkBuilder::new(&roots[0]);
+        for root in &roots[1..] {
+            builder.add(root);
+        }
+
+        builder
+            .standard_filters(false)
+            .follow_links(self.follow_links)
+            .same_file_system(self.same_file_system);
+
+        if self.gitignore {
+            builder
+                .git_ignore(true)
+                .git_exclude(true)
+                .git_global(true)
+                .parents(true)
+                .require_git(false);
+        }
+        for name in &self.ignore_files {
+            builder.add_custom_ignore_filename(name);
+        }
+
+        if !self.exclude.is_empty() {
+            let exclude = self.exclude;
+            let roots = roots.clone();
+            builder.filter_entry(move |entry| {
+                let path = relative(&roots, entry.path());
+                entry.depth() == 0 || !exclude.iter().any(|glob| glob.is_match(path))
+            });
+        }
+
+        Files {
+            inner: builder.build(),
+            roots,
+            include: self.include,
+            size: self.size,
+            modified: self.modified,
+        }
+    }
+}
+
+impl Files {
+    /// Returns the metadata of a file that passes the filters, or `None` to skip it.
+    fn select(&self, entry: &ignore::DirEntry) -> Result<Option<Metadata>, ImprintError> {
+        if !entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_file())
+        {
+            return Ok(None);
+        }
+
+        if !self.include.is_empty() {
+            let path = relative(&self.roots, entry.path());
+            if !self.include.iter().any(|glob| glob.is_match(path)) {
+                return Ok(None);
+            }
+        }
+
+        let meta = entry.metadata().map_err(walk_error)?;
+        if !self.size.contains(&meta.len()) {
+            return Ok(None);
+        }
+        if self.modified != (Bound::Unbounded, Bound::Unbounded) {
+            let modified = meta
+                .modified()
+                .map_err(|e| ImprintError::from(e).with_path(entry.path()))?;
+            if !self.modified.contains(&modified) {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(meta))
+    }
+}
+
+impl fmt::Debug for Walk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Walk")
+            .field("files", &self.files)
+            .field("imprinter", &self.imprinter)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Files {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Files")
+            .field("roots", &self.roots)
+            .field("include", &self.include)
+            .field("size", &self.size)
+            .field("modified", &self.modified)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Iterator for Walk {
+    type Item = Result<WalkEntry, ImprintError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (path, metadata) = match self.files.next()? {
+            Ok(file) => file,
+            Err(e) => return Some(Err(e)),
+        };
+
+        Some(self.imprinter.imprint(&path).map(|imprint| WalkEntry {
+            path,
+            metadata,
+            imprint,
+        }))
+    }
+}
+
+impl Iterator for Files {
+    type Item = Result<(PathBuf, Metadata), ImprintError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = match self.inner.next()? {
+                Ok(entry) => entry,
+                Err(e) => return Some(Err(walk_error(e))),
+            };
+
+            let metadata = match self.select(&entry) {
+                Ok(Some(metadata)) => metadata,
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            };
+
+            return Some(Ok((entry.into_path(), metadata)));
+        }
+    }
+}
+
+/// The path relative to the root it was found under, or the file name of a root itself.
+fn relative<'a>(roots: &[PathBuf], path: &'a Path) -> &'a Path {
+    roots
+        .iter()
+        .find_map(|root| path.strip_prefix(root).ok())
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .or_else(|| path.file_name().map(Path::new))
+        .unwrap_or(path)
+}
+
+/// The error for a file whose relative path is shared with a file found under an earlier root.
+fn collision(path: &Path, relative: &Path) -> ImprintError {
+    let source = io::Error::new(
+        ErrorKind::AlreadyExists,
+        format!("another root has a file at {}", relative.display()),
+    );
+    ImprintError::from(source).with_path(path)
+}
+
+fn walk_error(error: ignore::Error) -> ImprintError {
+    let path = error_path(&error);
+    let source = match error.io_error() {
+        Some(_) => os_error(error.into_io_error().unwrap()),
+        None => io::Error::other(error),
+    };
+
+    let error = ImprintError::from(source);
+    match path {
+        Some(path) => error.with_path(&path),
+        None => error,
+    }
+}
+
+/// Unwraps the OS error from an error that repeats the path in its own message.
+fn os_error(error: io::Error) -> io::Error {
+    let code = error
+        .get_ref()
+        .and_then(|inner| inner.source())
+        .and_then(|source| source.downcast_ref::<io::Error>())
+        .and_then(io::Error::raw_os_error);
+    match code {
+        Some(code) => io::Error::from_raw_os_error(code),
+        None => error,
+    }
+}
+
+fn error_path(error: &ignore::Error) -> Option<PathBuf> {
+    match error {
+        ignore::Error::WithPath { path, .. } => Some(path.clone()),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            error_path(err)
+        }
+        ignore::Error::Loop { child, .. } => Some(child.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn roots_sharing_a_relative_path_are_reported() {
+        let base = std::env::temp_dir().join(format!("imprint-roots-{}", std::process::id()));
+        let (first, second) = (base.join("first"), base.join("second"));
+        fs::create_dir_all(&first).unwrap();
+        fs::create_dir_all(&second).unwrap();
+        fs::write(first.join("shared"), b"first").unwrap();
+        fs::write(second.join("shared"), b"second").unwrap();
+        fs::write(second.join("other"), b"other").unwrap();
+
+        let walker = Walker::new(&first).root(&second);
+        let (manifest, errors) = walker.clone().snapshot();
+        assert_eq!(manifest.len(), 2);
+        assert_eq!(
+            manifest.get("shared"),
+            Some(&Imprint::from_memory(b"first").unwrap())
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path(), Some(second.join("shared").as_path()));
+
+        let verification = walker.verify(&manifest);
+        assert_eq!(verification.unchanged().len(), 2);
+        assert_eq!(verification.errors().len(), 1);
+        assert!(verification.added().is_empty());
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn walks_are_debug() {
+        let root = std::env::temp_dir();
+        assert!(format!("{:?}", Walker::new(&root).walk()).starts_with("Walk {"));
+        assert!(format!("{:?}", Walker::new(&root).files()).starts_with("Files {"));
+    }
+}
@@ -0,0 +1,169 @@
+use std::{fs, path::Path, time::SystemTime};
+
+use crate::{
+    stamp::{decode_time, encode_time, take, Stamp},
+    Imprint, ImprintError, Imprinter,
+};
+
+/// The extended attribute in which imprints are stored.
+pub const XATTR_NAME: &str = "user.imprint";
+
+/// The version of the format of the attribute's value.
+const FORMAT: u8 = 1;
+
+/// Stores imprints alongside the files they describe, in the `user.imprint` extended
+/// attribute, so that tools sharing a filesystem can reuse each other's work.
+///
+/// A stored imprint records the length and modification time of the file it was made from,
+/// and is used only while both are unchanged and it was made with the store's sampling and
+/// hashing scheme. The change time cannot be recorded, since storing the attribute changes it,
+/// so a file rewritten by a tool that restores its modification time is not detected.
+///
+/// Imprints are only read unless writing is enabled. Filesystems without extended attributes
+/// are tolerated: files on them are simply imprinted every time. The store is available only on
+/// Unix, with the `xattr` feature enabled.
+#[derive(Clone, Debug, Default)]
+pub struct XattrStore {
+    imprinter: Imprinter,
+    write: bool,
+}
+
+impl XattrStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the imprinter used for files without a valid stored imprint.
+    pub fn imprinter(mut self, imprinter: Imprinter) -> Self {
+        self.imprinter = imprinter;
+        self
+    }
+
+    /// Sets whether new imprints are stored on the files they describe. By default, nothing is
+    /// written.
+    ///
+    /// Failures to store an imprint, such as on a filesystem without extended attributes or a
+    /// file the process may not modify, are ignored.
+    pub fn write(mut self, yes: bool) -> Self {
+        self.write = yes;
+        self
+    }
+
+    /// Reads the imprint stored on the file at `path`, if there is one and it is still valid.
+    pub fn read(&self, path: impl AsRef<Path>) -> Result<Option<Imprint>, ImprintError> {
+        let path = path.as_ref();
+        Ok(self.stored(path, &stamp(path)?))
+    }
+
+    /// Imprints the file at `path`, reading its content only if it has no valid stored imprint.
+    ///
+    /// If writing is enabled, a new imprint is stored on the file unless the file changed while
+    /// it was imprinted.
+    pub fn imprint(&mut self, path: impl AsRef<Path>) -> Result<Imprint, ImprintError> {
+        let path = path.as_ref();
+        let before = stamp(path)?;
+        if let Some(imprint) = self.stored(path, &before) {
+            return Ok(imprint);
+        }
+
+        let started = SystemTime::now();
+        let imprint = self.imprinter.imprint(path)?;
+        if self.write && stamp(path)? == before && !before.is_racy(started) {
+            let _ = xattr::set_deref(path, XATTR_NAME, &encode(&before, &imprint));
+        }
+        Ok(imprint)
+    }
+
+    fn stored(&self, path: &Path, stamp: &Stamp) -> Option<Imprint> {
+        let value = xattr::get_deref(path, XATTR_NAME).ok()??;
+        let (len, modified, imprint) = decode(&value)?;
+        let valid = len == stamp.len
+            && modified.is_some()
+            && modified == stamp.modified
+            && imprint.len == len
+            && self.imprinter.would_make(&imprint);
+        if valid {
+            Some(imprint)
+        } else {
+            None
+        }
+    }
+}
+
+fn stamp(path: &Path) -> Result<Stamp, ImprintError> {
+    let meta = fs::metadata(path).map_err(|e| ImprintError::from(e).with_path(path))?;
+    ImprintError::check_file(path, &meta)?;
+    Ok(Stamp::new(&meta))
+}
+
+fn encode(stamp: &Stamp, imprint: &Imprint) -> Vec<u8> {
+    let mut value = vec![FORMAT];
+    value.extend_from_slice(&stamp.len.to_le_bytes());
+    encode_time(&mut value, stamp.modified);
+    value.extend_from_slice(&imprint.to_bytes());
+    value
+}
+
+fn decode(value: &[u8]) -> Option<(u64, Option<SystemTime>, Imprint)> {
+    let (&format, mut rest) = value.split_first()?;
+    if format != FORMAT {
+        return None;
+    }
+
+    let len = u64::from_le_bytes(take(&mut rest)?);
+    let modified = decode_time(&mut rest)?;
+    let imprint = Imprint::from_bytes(rest).ok()?;
+    Some((len, modified, imprint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crafted_times_are_rejected() {
+        let imprint = Imprint::from_memory(b"content").unwrap();
+        let mut value = vec![FORMAT];
+        value.extend_from_slice(&imprint.len().to_le_bytes());
+        value.push(1);
+        value.extend_from_slice(&u64::MAX.to_le_bytes());
+        value.extend_from_slice(&u32::MAX.to_le_bytes());
+        value.extend_from_slice(&imprint.to_bytes());
+        assert!(decode(&value).is_none());
+    }
+
+    #[test]
+    fn read_tolerates_crafted_attribute() {
+        let path = std::env::temp_dir().join(format!("imprint-crafted-{}", std::process::id()));
+        fs::write(&path, b"content").unwrap();
+
+        let imprint = Imprint::from_memory(b"content").unwrap();
+        let mut value = vec![FORMAT];
+        value.extend_from_slice(&imprint.len().to_le_bytes());
+        value.push(1);
+        value.extend_from_slice(&u64::MAX.to_le_bytes());
+        value.extend_from_slice(&u32::MAX.to_le_bytes());
+        value.extend_from_slice(&imprint.to_bytes());
+
+        // Filesystems without extended attributes have nothing to read.
+        if xattr::set(&path, XATTR_NAME, &value).is_ok() {
+            assert_eq!(XattrStore::new().read(&path).unwrap(), None);
+            assert_eq!(XattrStore::new().imprint(&path).unwrap(), imprint);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let path = std::env::temp_dir().join(format!("imprint-xattr-{}", std::process::id()));
+        fs::write(&path, b"content").unwrap();
+        let stamp = stamp(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let imprint = Imprint::from_memory(b"content").unwrap();
+        let (len, modified, decoded) = decode(&encode(&stamp, &imprint)).unwrap();
+        assert_eq!(len, stamp.len);
+        assert_eq!(modified, stamp.modified);
+        assert_eq!(decoded, imprint);
+    }
+}